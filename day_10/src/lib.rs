@@ -1,9 +1,9 @@
-use std::collections::HashSet;
-
-const GRID_SIZE: usize = 139;
+use std::collections::{HashSet, VecDeque};
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read};
 
 #[derive(Eq, PartialEq)]
-enum Tile {
+pub enum Tile {
     Vertical,
     Horizontal,
     NorthAndEast,
@@ -63,18 +63,18 @@ fn parse_tile(c: char) -> Option<Tile> {
     }
 }
 
-#[derive(Debug)]
-struct Pair(usize, usize);
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pair(pub usize, pub usize);
 struct Node {
     visited: bool,
     tile: Tile,
 }
-struct Graph {
+pub struct Graph {
     nodes: Vec<Vec<Node>>,
 }
 
 impl Graph {
-    fn new(tiles: Vec<Vec<Tile>>) -> Self {
+    pub fn new(tiles: Vec<Vec<Tile>>) -> Self {
         Self {
             nodes: tiles
                 .into_iter()
@@ -104,6 +104,8 @@ impl Graph {
     fn get_neighbors(&self, pair: &Pair) -> Vec<Pair> {
         let Pair(x, y) = pair;
         let node = self.get_node(pair);
+        let max_y = self.nodes.len() - 1;
+        let max_x = self.nodes[*y].len() - 1;
         let direction_options = directions(&node.tile);
 
         direction_options
@@ -111,8 +113,8 @@ impl Graph {
             .filter(|direction| -> bool {
                 match direction {
                     Direction::North => *y > 0,
-                    Direction::South => *y < GRID_SIZE,
-                    Direction::East => *x < GRID_SIZE,
+                    Direction::South => *y < max_y,
+                    Direction::East => *x < max_x,
                     Direction::West => *x > 0,
                 }
             })
@@ -139,64 +141,245 @@ impl Graph {
             })
             .collect()
     }
+
+    /// The pipe hidden under S, worked out from the two neighbours that connect back to it.
+    pub fn start_shape(&self, start: &Pair) -> Result<Tile, Box<dyn Error>> {
+        let Pair(x, y) = start;
+        let connected: Vec<Direction> = self
+            .get_neighbors(start)
+            .into_iter()
+            .map(|Pair(nx, ny)| match (nx.cmp(x), ny.cmp(y)) {
+                (_, std::cmp::Ordering::Less) => Direction::North,
+                (_, std::cmp::Ordering::Greater) => Direction::South,
+                (std::cmp::Ordering::Greater, _) => Direction::East,
+                _ => Direction::West,
+            })
+            .collect();
+        let connects = |direction| connected.contains(&direction);
+        if connected.len() != 2 {
+            return Err("Start doesn't connect to exactly two pipes".into());
+        }
+        Ok(
+            match (
+                connects(Direction::North),
+                connects(Direction::South),
+                connects(Direction::East),
+            ) {
+                (true, true, _) => Tile::Vertical,
+                (true, _, true) => Tile::NorthAndEast,
+                (true, _, _) => Tile::NorthAndWest,
+                (_, true, true) => Tile::SouthAndEast,
+                (_, true, _) => Tile::SouthAndWest,
+                _ => Tile::Horizontal,
+            },
+        )
+    }
+
+    /// Every tile of the loop through `start`, in walking order.
+    pub fn loop_tiles(&self, start: &Pair) -> Result<Vec<Pair>, Box<dyn Error>> {
+        self.start_shape(start)?;
+        let mut tiles = vec![*start];
+        let mut previous: Option<Pair> = None;
+        let mut current = *start;
+        loop {
+            let next = self
+                .get_neighbors(&current)
+                .into_iter()
+                .find(|neighbor| Some(*neighbor) != previous)
+                .ok_or("Loop is broken")?;
+            if next == *start {
+                return Ok(tiles);
+            }
+            tiles.push(next);
+            previous = Some(current);
+            current = next;
+        }
+    }
+}
+
+pub fn parse(input: impl Read) -> Result<(Graph, Pair), Box<dyn Error>> {
+    let mut starting_pair: Option<Pair> = None;
+    let mut tiles: Vec<Vec<Tile>> = Vec::new();
+    for (i, line) in BufReader::new(input).lines().enumerate() {
+        let mut row = Vec::new();
+        for (j, c) in line?.chars().enumerate() {
+            let tile = parse_tile(c).ok_or("Invalid char")?;
+            if tile == Tile::AnimalStart {
+                starting_pair = Some(Pair(j, i));
+            }
+            row.push(tile);
+        }
+        tiles.push(row);
+    }
+    let starting_pair = starting_pair.ok_or("Unable to find animal starting point")?;
+    Ok((Graph::new(tiles), starting_pair))
+}
+
+pub fn solve_part1(graph: &mut Graph, starting_pair: Pair) -> usize {
+    // BFS until we find the max
+    let mut deq: VecDeque<Pair> = VecDeque::new();
+    deq.push_back(starting_pair);
+    let mut distance = 0usize;
+
+    while !deq.is_empty() {
+        let mut next_deq: VecDeque<Pair> = VecDeque::new();
+        for pair in deq {
+            // visit node
+            graph.visit(&pair);
+            let neighbors = graph.get_neighbors(&pair);
+            for neighbor in neighbors {
+                let neighbor_node = graph.get_node(&neighbor);
+                if !neighbor_node.visited {
+                    next_deq.push_back(neighbor);
+                }
+            }
+        }
+
+        distance += 1;
+        deq = next_deq;
+    }
+
+    distance - 1
+}
+
+/// Tiles inside the loop by scanning each row and flipping inside/outside on every loop tile
+/// with a northward connection, which counts each vertical crossing exactly once.
+pub fn enclosed_by_scanline(graph: &Graph, start: &Pair) -> Result<usize, Box<dyn Error>> {
+    let start_shape = graph.start_shape(start)?;
+    let mut on_loop: Vec<Vec<bool>> = graph
+        .nodes
+        .iter()
+        .map(|row| vec![false; row.len()])
+        .collect();
+    for Pair(x, y) in graph.loop_tiles(start)? {
+        on_loop[y][x] = true;
+    }
+
+    let mut enclosed = 0;
+    for (y, row) in graph.nodes.iter().enumerate() {
+        let mut inside = false;
+        for (x, node) in row.iter().enumerate() {
+            if !on_loop[y][x] {
+                enclosed += inside as usize;
+                continue;
+            }
+            let tile = match node.tile {
+                Tile::AnimalStart => &start_shape,
+                ref tile => tile,
+            };
+            if directions(tile).contains(&Direction::North) {
+                inside = !inside;
+            }
+        }
+    }
+    Ok(enclosed)
+}
+
+/// Tiles inside the loop from its area by the shoelace formula, then Pick's theorem:
+/// area = interior + boundary / 2 - 1.
+pub fn enclosed_by_pick(graph: &Graph, start: &Pair) -> Result<usize, Box<dyn Error>> {
+    let tiles = graph.loop_tiles(start)?;
+    let twice_area = tiles
+        .iter()
+        .zip(tiles.iter().cycle().skip(1))
+        .map(|(Pair(x1, y1), Pair(x2, y2))| (*x1 * *y2) as i64 - (*x2 * *y1) as i64)
+        .sum::<i64>()
+        .unsigned_abs() as usize;
+    Ok((twice_area + 2 - tiles.len()) / 2)
+}
+
+pub fn solve_part2(graph: &Graph, starting_pair: &Pair) -> Result<usize, Box<dyn Error>> {
+    let scanline = enclosed_by_scanline(graph, starting_pair)?;
+    let pick = enclosed_by_pick(graph, starting_pair)?;
+    if scanline != pick {
+        return Err(format!(
+            "Scanline found {scanline} enclosed tiles but Pick's theorem found {pick}"
+        )
+        .into());
+    }
+    Ok(scanline)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{parse_tile, Graph, Pair, Tile};
-    use std::collections::VecDeque;
+    use super::*;
     use std::error::Error;
-    use std::fs::File;
-    use std::io::{BufRead, BufReader};
+
+    const SAMPLE_1: &str = ".....
+.S-7.
+.|.|.
+.L-J.
+.....
+";
+
+    const SAMPLE_2: &str = "..F7.
+.FJ|.
+SJ.L7
+|F--J
+LJ...
+";
 
     #[test]
     fn part_1() -> Result<(), Box<dyn Error>> {
-        let mut starting_pair: Option<Pair> = None;
-        let tiles: Vec<Vec<Tile>> = BufReader::new(File::open("input")?)
-            .lines()
-            .enumerate()
-            .map(|(i, line_res)| -> Vec<Tile> {
-                let line = line_res.expect("Unable to read line");
-                line.chars()
-                    .enumerate()
-                    .map(|(j, c)| {
-                        let tile = parse_tile(c).expect("Invalid char");
-                        if tile == Tile::AnimalStart {
-                            starting_pair = Some(Pair(j, i));
-                        }
-                        tile
-                    })
-                    .collect()
-            })
-            .collect();
+        let (mut graph, starting_pair) = parse(SAMPLE_1.as_bytes())?;
+        assert_eq!(solve_part1(&mut graph, starting_pair), 4);
+        let (mut graph, starting_pair) = parse(SAMPLE_2.as_bytes())?;
+        assert_eq!(solve_part1(&mut graph, starting_pair), 8);
+        Ok(())
+    }
 
-        // BFS until we find the max
-        let mut graph = Graph::new(tiles);
-        let mut deq: VecDeque<Pair> = VecDeque::new();
-        deq.push_back(starting_pair.expect("Unable to find animal starting point"));
-        let mut distance = 0usize;
-
-        while !deq.is_empty() {
-            let mut next_deq: VecDeque<Pair> = VecDeque::new();
-            for pair in deq {
-                // visit node
-                let node = graph.get_node(&pair);
-                graph.visit(&pair);
-                let neighbors = graph.get_neighbors(&pair);
-                for neighbor in neighbors {
-                    let neighbor_node = graph.get_node(&neighbor);
-                    if !neighbor_node.visited {
-                        next_deq.push_back(neighbor);
-                    }
-                }
-            }
+    const ENCLOSED_4: &str = "...........
+.S-------7.
+.|F-----7|.
+.||.....||.
+.||.....||.
+.|L-7.F-J|.
+.|..|.|..|.
+.L--J.L--J.
+...........
+";
 
-            distance += 1;
-            deq = next_deq;
-        }
+    const ENCLOSED_8: &str = ".F----7F7F7F7F-7....
+.|F--7||||||||FJ....
+.||.FJ||||||||L7....
+FJL7L7LJLJ||LJ.L-7..
+L--J.L7...LJS7F-7L7.
+....F-J..F7FJ|L7L7L7
+....L7.F7||L7|.L7L7|
+.....|FJLJ|FJ|F7|.LJ
+....FJL-7.||.||||...
+....L---J.LJ.LJLJ...
+";
 
-        println!("max distance: {}", distance - 1);
+    // S is a 7 here, so it must not count as a crossing
+    const ENCLOSED_10: &str = "FF7FSF7F7F7F7F7F---7
+L|LJ||||||||||||F--J
+FL-7LJLJ||||||LJL-77
+F--JF--7||LJLJ7F7FJ-
+L---JF-JLJ.||-FJLJJ7
+|F|F-JF---7F7-L7L|7|
+|FFJF7L7F-JF7|JL---7
+7-L-JL7||F7|L7F-7F7|
+L.L7LFJ|||||FJL7||LJ
+L7JLJL-JLJLJL--JLJ.L
+";
 
+    #[test]
+    fn part_2() -> Result<(), Box<dyn Error>> {
+        for (sample, expected) in [
+            (SAMPLE_1, 1),
+            (ENCLOSED_4, 4),
+            (ENCLOSED_8, 8),
+            (ENCLOSED_10, 10),
+        ] {
+            let (graph, starting_pair) = parse(sample.as_bytes())?;
+            assert_eq!(enclosed_by_scanline(&graph, &starting_pair)?, expected);
+            assert_eq!(enclosed_by_pick(&graph, &starting_pair)?, expected);
+            assert_eq!(solve_part2(&graph, &starting_pair)?, expected);
+        }
+        let (graph, starting_pair) = parse(ENCLOSED_10.as_bytes())?;
+        assert!(graph.start_shape(&starting_pair)? == Tile::SouthAndWest);
+        assert_eq!(graph.loop_tiles(&starting_pair)?.len() % 2, 0);
         Ok(())
     }
 }
@@ -0,0 +1,15 @@
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{stdin, Read};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let input: Box<dyn Read> = match env::args().nth(1) {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(stdin().lock()),
+    };
+    let (mut graph, starting_pair) = day_10::parse(input)?;
+    println!("Part 1: {}", day_10::solve_part1(&mut graph, starting_pair));
+    println!("Part 2: {}", day_10::solve_part2(&graph, &starting_pair)?);
+    Ok(())
+}
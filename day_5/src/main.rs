@@ -1,48 +1,242 @@
-use std::cmp::min;
+use std::env;
+use std::error::Error;
 use std::io::{stdin, BufRead, BufReader};
 
-fn main() {
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct MapRange {
+    dest_start: u64,
+    source_start: u64,
+    length: u64,
+}
+
+impl MapRange {
+    fn contains(&self, num: u64) -> bool {
+        num >= self.source_start && num - self.source_start < self.length
+    }
+
+    // Only meaningful for numbers the range contains. None if the output doesn't fit in a u64
+    fn map(&self, num: u64) -> Option<u64> {
+        let diff = num - self.source_start;
+        self.dest_start.checked_add(diff)
+    }
+}
+
+struct Map {
+    name: String,
+    ranges: Vec<MapRange>,
+}
+
+impl Map {
+    fn map(&self, num: u64) -> Option<u64> {
+        // First matching range wins, anything unmatched maps to itself
+        match self.ranges.iter().find(|range| range.contains(num)) {
+            Some(range) => range.map(num),
+            None => Some(num),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum MapIssue {
+    // Two ranges claim the same source numbers, so only the first one is ever used
+    Overlap {
+        first: usize,
+        second: usize,
+        start: u64,
+        end: u64,
+    },
+    SourceOverflow {
+        index: usize,
+    },
+    DestOverflow {
+        index: usize,
+    },
+    // Source numbers not covered by any range, which fall through unchanged
+    Gap {
+        start: u64,
+        end: u64,
+    },
+}
+
+fn lint_map(map: &Map, include_gaps: bool) -> Vec<MapIssue> {
+    let mut issues = Vec::new();
+    // Exclusive source end for each range, or None if it doesn't fit in a u64
+    let mut source_ends: Vec<Option<u64>> = Vec::with_capacity(map.ranges.len());
+    for (index, range) in map.ranges.iter().enumerate() {
+        let source_end = range.source_start.checked_add(range.length);
+        if source_end.is_none() {
+            issues.push(MapIssue::SourceOverflow { index });
+        }
+        // Largest output is dest_start + (length - 1)
+        if range.length > 0 && range.dest_start.checked_add(range.length - 1).is_none() {
+            issues.push(MapIssue::DestOverflow { index });
+        }
+        source_ends.push(source_end);
+    }
+
+    for i in 0..map.ranges.len() {
+        for j in (i + 1)..map.ranges.len() {
+            let start = map.ranges[i].source_start.max(map.ranges[j].source_start);
+            let end = match (source_ends[i], source_ends[j]) {
+                (Some(a), Some(b)) => a.min(b),
+                (Some(a), None) | (None, Some(a)) => a,
+                (None, None) => u64::MAX,
+            };
+            if start < end {
+                issues.push(MapIssue::Overlap {
+                    first: i,
+                    second: j,
+                    start,
+                    end,
+                });
+            }
+        }
+    }
+
+    if include_gaps {
+        let mut sorted: Vec<(u64, u64)> = map
+            .ranges
+            .iter()
+            .zip(source_ends.iter())
+            .filter(|(range, _)| range.length > 0)
+            .map(|(range, end)| (range.source_start, end.unwrap_or(u64::MAX)))
+            .collect();
+        sorted.sort();
+        let mut covered_to = 0u64;
+        for (start, end) in sorted {
+            if start > covered_to {
+                issues.push(MapIssue::Gap {
+                    start: covered_to,
+                    end: start,
+                });
+            }
+            covered_to = covered_to.max(end);
+        }
+        if covered_to < u64::MAX {
+            issues.push(MapIssue::Gap {
+                start: covered_to,
+                end: u64::MAX,
+            });
+        }
+    }
+    issues
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let include_gaps = env::args().any(|arg| arg == "--gaps");
     let mut lines = BufReader::new(stdin().lock()).lines();
     let seed_string: String = lines
         .next()
         .expect("Missing seed line")
         .expect("Unable to read seeds");
-    let seed_strings: Vec<&str> = seed_string.split(" ").collect();
-    let mut seeds: Vec<(u64, bool)> = (&seed_strings[1..])
+    let seed_strings: Vec<&str> = seed_string.split(' ').collect();
+    let seeds: Vec<u64> = seed_strings[1..]
         .iter()
-        .map(|s| (s.parse().expect("Invalid seed"), false))
+        .map(|s| s.parse().expect("Invalid seed"))
         .collect();
     lines.next(); // Advance past empty line
 
-    // Traverse each layer until we make it to location
-    while let Some(Ok(line)) = lines.next() {
-        let segments: Vec<&str> = line.split(" ").collect();
+    let mut maps: Vec<Map> = Vec::new();
+    for line in lines.map_while(Result::ok) {
+        let segments: Vec<&str> = line.split(' ').collect();
         match segments.as_slice() {
             [dest_start_string, source_start_string, length_string] => {
-                let dest_start: u64 = dest_start_string.parse().expect("Invalid dest start");
-                let source_start: u64 = source_start_string.parse().expect("Invalid source start");
-                let length: u64 = length_string.parse().expect("Invalid length");
-                seeds = seeds
-                    .iter()
-                    .map(|(num, seen)| -> (u64, bool) {
-                        return if !seen && *num >= source_start && *num < source_start + length {
-                            let diff = num - source_start;
-                            (dest_start + diff, true)
-                        } else {
-                            (*num, *seen)
-                        }
-                    })
-                    .collect();
-            }
-            [_, "map:"] => {}
-            [""] => {
-                // Reset for next map
-                seeds = seeds.iter().map(|seed| (seed.0, false)).collect();
+                let range = MapRange {
+                    dest_start: dest_start_string.parse().expect("Invalid dest start"),
+                    source_start: source_start_string.parse().expect("Invalid source start"),
+                    length: length_string.parse().expect("Invalid length"),
+                };
+                maps.last_mut()
+                    .expect("Range before map header")
+                    .ranges
+                    .push(range);
             }
+            [name, "map:"] => maps.push(Map {
+                name: name.to_string(),
+                ranges: Vec::new(),
+            }),
+            [""] => {}
             _ => panic!("Bad line :("),
         }
     }
 
-    let min_location = seeds.iter().min_by(|x, y| x.0.cmp(&y.0));
-    println!("Min location: {}", min_location.expect("No min location!").0);
+    for map in &maps {
+        for issue in lint_map(map, include_gaps) {
+            eprintln!("{}: {:?}", map.name, issue);
+        }
+    }
+
+    // Traverse each layer until we make it to location
+    let locations = seeds
+        .iter()
+        .map(|seed| {
+            maps.iter()
+                .try_fold(*seed, |num, map| map.map(num))
+                .ok_or_else(|| format!("Seed {seed} maps past u64::MAX"))
+        })
+        .collect::<Result<Vec<u64>, String>>()?;
+    let min_location = locations.iter().min().ok_or("No min location!")?;
+    println!("Min location: {min_location}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_map(ranges: &[(u64, u64, u64)]) -> Map {
+        Map {
+            name: String::from("test"),
+            ranges: ranges
+                .iter()
+                .map(|(dest_start, source_start, length)| MapRange {
+                    dest_start: *dest_start,
+                    source_start: *source_start,
+                    length: *length,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn lint_sample_map() {
+        // seed-to-soil map from the puzzle example
+        let map = make_map(&[(50, 98, 2), (52, 50, 48)]);
+        assert_eq!(lint_map(&map, false), vec![]);
+        assert_eq!(
+            lint_map(&map, true),
+            vec![
+                MapIssue::Gap { start: 0, end: 50 },
+                MapIssue::Gap {
+                    start: 100,
+                    end: u64::MAX
+                },
+            ]
+        );
+        assert_eq!(map.map(79), Some(81));
+        assert_eq!(map.map(14), Some(14));
+    }
+
+    #[test]
+    fn lint_overlap_and_overflow() {
+        let map = make_map(&[(0, 10, 10), (100, 15, 10), (u64::MAX, u64::MAX - 1, 5)]);
+        assert_eq!(
+            lint_map(&map, false),
+            vec![
+                MapIssue::SourceOverflow { index: 2 },
+                MapIssue::DestOverflow { index: 2 },
+                MapIssue::Overlap {
+                    first: 0,
+                    second: 1,
+                    start: 15,
+                    end: 20
+                },
+            ]
+        );
+        // First match still wins
+        assert_eq!(map.map(16), Some(6));
+        // The flagged range maps until its output runs out of room
+        assert_eq!(map.map(u64::MAX - 1), Some(u64::MAX));
+        assert_eq!(map.map(u64::MAX), None);
+    }
 }
@@ -1,63 +1,108 @@
+use boats::{BoatModel, Classic};
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read};
 
-struct Race {
-    time: u64,
-    distance: u64,
+pub struct Race {
+    pub time: u64,
+    pub distance: u64,
 }
 
-fn read_input() -> Result<Vec<Race>, Box<dyn Error>> {
-    let mut lines = BufReader::new(File::open("input")?).lines();
-    let times: Vec<u64> = lines
-        .next()
-        .expect("Missing times")?
-        .split_whitespace()
-        .skip(1)
-        .map(|s| s.parse().expect("Bad time value"))
-        .collect();
-    let distances: Vec<u64> = lines
-        .next()
-        .expect("Missing distances")?
+#[derive(Debug, PartialEq)]
+pub struct RaceReport {
+    pub num_winners: u64,
+    pub first_winning_hold: Option<u64>,
+    pub last_winning_hold: Option<u64>,
+    pub optimal_hold: u64,
+    pub best_distance: u128,
+    // How much the record could rise with the race still winnable
+    pub record_headroom: Option<u128>,
+}
+
+impl Race {
+    pub fn report(&self) -> RaceReport {
+        let (optimal_hold, best_distance) = Classic.optimal_hold(self);
+        let winning_holds = Classic.winning_holds(self);
+        RaceReport {
+            num_winners: get_num_winners(self),
+            first_winning_hold: winning_holds.as_ref().map(|holds| *holds.start()),
+            last_winning_hold: winning_holds.as_ref().map(|holds| *holds.end()),
+            optimal_hold,
+            best_distance,
+            record_headroom: Classic.margin(self).map(|margin| margin - 1),
+        }
+    }
+}
+
+/// The races as read, plus the raw number tokens so part 2 can join the digits exactly as
+/// written, leading zeros included.
+pub struct Sheet {
+    pub races: Vec<Race>,
+    pub times: Vec<String>,
+    pub distances: Vec<String>,
+}
+
+fn read_tokens(
+    line: Option<std::io::Result<String>>,
+    missing: &str,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(line
+        .ok_or(missing)??
         .split_whitespace()
         .skip(1)
-        .map(|s| s.parse().expect("Bad distances value"))
-        .collect();
+        .map(String::from)
+        .collect())
+}
+
+pub fn parse(input: impl Read) -> Result<Sheet, Box<dyn Error>> {
+    let mut lines = BufReader::new(input).lines();
+    let times = read_tokens(lines.next(), "Missing times")?;
+    let distances = read_tokens(lines.next(), "Missing distances")?;
     let races = times
         .iter()
         .zip(distances.iter())
-        .map(|pair| Race {
-            time: *pair.0,
-            distance: *pair.1,
+        .map(|pair| -> Result<Race, Box<dyn Error>> {
+            Ok(Race {
+                time: pair.0.parse()?,
+                distance: pair.1.parse()?,
+            })
         })
-        .collect();
-    Ok(races)
+        .collect::<Result<_, _>>()?;
+    Ok(Sheet {
+        races,
+        times,
+        distances,
+    })
 }
 
-fn read_actual_input() -> Result<Race, Box<dyn Error>> {
-    let mut lines = BufReader::new(File::open("input")?).lines();
-    let time: u64 = lines
-        .next()
-        .expect("Missing time line")?
-        .split_whitespace()
-        .skip(1)
-        .collect::<Vec<&str>>()
-        .join("")
-        .parse()
-        .expect("Invalid time as u64");
-    let distance: u64 = lines
-        .next()
-        .expect("Missing distance line")?
-        .split_whitespace()
-        .skip(1)
-        .collect::<Vec<&str>>()
-        .join("")
-        .parse()
-        .expect("Invalid distance as u64");
-    Ok(Race { time, distance })
+// The sheet actually describes one race, with bad kerning splitting up the digits
+pub fn fix_kerning(sheet: &Sheet) -> Result<Race, Box<dyn Error>> {
+    Ok(Race {
+        time: sheet.times.concat().parse()?,
+        distance: sheet.distances.concat().parse()?,
+    })
+}
+
+pub fn solve_part1(races: &[Race]) -> u64 {
+    races.iter().map(get_num_winners).product()
+}
+
+pub fn solve_part2(sheet: &Sheet) -> Result<u64, Box<dyn Error>> {
+    Ok(get_num_winners(&fix_kerning(sheet)?))
+}
+
+pub fn report_part1(races: &[Race]) -> Vec<RaceReport> {
+    races.iter().map(Race::report).collect()
+}
+
+pub fn report_part2(sheet: &Sheet) -> Result<RaceReport, Box<dyn Error>> {
+    Ok(fix_kerning(sheet)?.report())
 }
 
-fn get_num_winners(race: &Race) -> u64 {
+pub fn get_num_winners(race: &Race) -> u64 {
+    count_winning_holds(race.time as u128, race.distance as u128) as u64
+}
+
+pub fn get_num_winners_brute_force(race: &Race) -> u64 {
     let mut winning_ways = 0u64;
     for time_held in 1..race.time {
         let seconds_to_race = race.time - time_held;
@@ -68,29 +113,319 @@ fn get_num_winners(race: &Race) -> u64 {
     winning_ways
 }
 
+// Multiply two u128s into a (high, low) pair of u128s
+fn mul_wide(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_hi, a_lo) = (a >> 64, a & MASK);
+    let (b_hi, b_lo) = (b >> 64, b & MASK);
+    let lo_lo = a_lo * b_lo;
+    let hi_lo = a_hi * b_lo;
+    let lo_hi = a_lo * b_hi;
+    let hi_hi = a_hi * b_hi;
+    let middle = (lo_lo >> 64) + (hi_lo & MASK) + (lo_hi & MASK);
+    let lo = (lo_lo & MASK) | (middle << 64);
+    let hi = hi_hi + (hi_lo >> 64) + (lo_hi >> 64) + (middle >> 64);
+    (hi, lo)
+}
+
+// Largest s with s * s <= n, where n is given as a (high, low) pair
+fn isqrt_wide(n: (u128, u128)) -> u128 {
+    let mut root = 0u128;
+    for bit in (0..128).rev() {
+        let candidate = root | (1 << bit);
+        if mul_wide(candidate, candidate) <= n {
+            root = candidate;
+        }
+    }
+    root
+}
+
+// Whether holding for time_held strictly beats the record
+fn beats_record(time: u128, distance: u128, time_held: u128) -> bool {
+    match time_held.checked_mul(time - time_held) {
+        Some(travelled) => travelled > distance,
+        // Anything past u128::MAX beats a u128 record
+        None => true,
+    }
+}
+
+/// Number of hold times that strictly beat `distance` in a race lasting `time`.
+///
+/// Solves `h * (time - h) > distance` via the roots `(time ± sqrt(time^2 - 4 * distance)) / 2`,
+/// then nudges the lower root onto the first winning hold time so ties with the record lose.
+pub fn count_winning_holds(time: u128, distance: u128) -> u128 {
+    // time^2 - 4 * distance, using 256 bits so u128 races don't overflow
+    let time_squared = mul_wide(time, time);
+    let four_distance = (distance >> 126, distance << 2);
+    if time_squared <= four_distance {
+        return 0;
+    }
+    let (mut hi, lo) = (
+        time_squared.0 - four_distance.0,
+        time_squared.1.wrapping_sub(four_distance.1),
+    );
+    if time_squared.1 < four_distance.1 {
+        hi -= 1;
+    }
+    let root = isqrt_wide((hi, lo));
+
+    // The estimate is within one of the first winning hold time
+    let mut first = (time - root) / 2;
+    while first > 0 && beats_record(time, distance, first - 1) {
+        first -= 1;
+    }
+    while first <= time / 2 && !beats_record(time, distance, first) {
+        first += 1;
+    }
+    if first > time / 2 {
+        return 0;
+    }
+    // Distances are symmetric around time / 2
+    let last = time - first;
+    last - first + 1
+}
+
+pub mod boats {
+    use crate::Race;
+    use std::ops::RangeInclusive;
+
+    pub trait BoatModel {
+        /// Distance travelled when the button is held for `time_held` of a race lasting `time`.
+        ///
+        /// Must never rise again once it starts falling, so the winning hold times form one
+        /// interval around the optimum.
+        fn distance(&self, time: u64, time_held: u64) -> u128;
+
+        /// Hold time with the best distance, and that distance. Ties go to the shortest hold.
+        fn optimal_hold(&self, race: &Race) -> (u64, u128) {
+            // Find the first hold time where holding any longer stops helping
+            let (mut low, mut high) = (0, race.time);
+            while low < high {
+                let mid = low + (high - low) / 2;
+                if self.distance(race.time, mid) < self.distance(race.time, mid + 1) {
+                    low = mid + 1;
+                } else {
+                    high = mid;
+                }
+            }
+            (low, self.distance(race.time, low))
+        }
+
+        /// Every hold time that strictly beats the record, or None if the race can't be won.
+        fn winning_holds(&self, race: &Race) -> Option<RangeInclusive<u64>> {
+            let record = race.distance as u128;
+            let (optimal, best) = self.optimal_hold(race);
+            if best <= record {
+                return None;
+            }
+            // Distance only rises up to the optimum, so the first winner is the lowest
+            // hold time in 0..=optimal that beats the record
+            let (mut low, mut high) = (0, optimal);
+            while low < high {
+                let mid = low + (high - low) / 2;
+                if self.distance(race.time, mid) > record {
+                    high = mid;
+                } else {
+                    low = mid + 1;
+                }
+            }
+            let first = low;
+            // And only falls after it
+            let (mut low, mut high) = (optimal, race.time);
+            while low < high {
+                let mid = high - (high - low) / 2;
+                if self.distance(race.time, mid) > record {
+                    low = mid;
+                } else {
+                    high = mid - 1;
+                }
+            }
+            Some(first..=low)
+        }
+
+        /// How far the best distance beats the record by, or None if it doesn't.
+        fn margin(&self, race: &Race) -> Option<u128> {
+            let (_, best) = self.optimal_hold(race);
+            best.checked_sub(race.distance as u128)
+                .filter(|margin| *margin > 0)
+        }
+    }
+
+    /// Speed is the hold time, as in the puzzle.
+    pub struct Classic;
+
+    impl BoatModel for Classic {
+        fn distance(&self, time: u64, time_held: u64) -> u128 {
+            let speed = time_held.min(time) as u128;
+            speed * (time - time_held.min(time)) as u128
+        }
+    }
+
+    /// Speed grows with the square of the hold time.
+    pub struct QuadraticAcceleration;
+
+    impl BoatModel for QuadraticAcceleration {
+        fn distance(&self, time: u64, time_held: u64) -> u128 {
+            let time_held = time_held.min(time) as u128;
+            let speed = time_held * time_held;
+            speed.saturating_mul(time as u128 - time_held)
+        }
+    }
+
+    /// Speed is the hold time, up to `top_speed`.
+    pub struct CappedSpeed {
+        pub top_speed: u64,
+    }
+
+    impl BoatModel for CappedSpeed {
+        fn distance(&self, time: u64, time_held: u64) -> u128 {
+            let time_held = time_held.min(time);
+            let speed = time_held.min(self.top_speed) as u128;
+            speed * (time - time_held) as u128
+        }
+    }
+
+    /// Speed is the hold time, but the boat sits still for `penalty` milliseconds after release.
+    pub struct ChargePenalty {
+        pub penalty: u64,
+    }
+
+    impl BoatModel for ChargePenalty {
+        fn distance(&self, time: u64, time_held: u64) -> u128 {
+            let time_held = time_held.min(time);
+            let moving_time = (time - time_held).saturating_sub(self.penalty);
+            time_held as u128 * moving_time as u128
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::boats::*;
     use super::*;
 
+    const SAMPLE: &str = "Time:      7  15   30
+Distance:  9  40  200
+";
+
     #[test]
     fn part_one() {
-        let races = read_input();
-        let mut winning_combos: Vec<u64> = vec![];
-        for race in races.expect("Unable to read races") {
-            winning_combos.push(get_num_winners(&race));
-        }
-        let total = winning_combos
-            .iter()
-            .copied()
-            .reduce(|acc, e| acc * e)
-            .expect("");
-        println!("Total: {total}");
+        let sheet = parse(SAMPLE.as_bytes()).expect("Unable to read races");
+        assert_eq!(solve_part1(&sheet.races), 288);
     }
 
     #[test]
     fn part_two() {
-        let race = read_actual_input().expect("Unable to read race");
-        let winning_ways = get_num_winners(&race);
-        println!("Total: {winning_ways}");
+        let sheet = parse(SAMPLE.as_bytes()).expect("Unable to read races");
+        assert_eq!(solve_part2(&sheet).expect("Unable to fix kerning"), 71503);
+
+        // Digits are joined as written, so leading zeros stay put
+        let sheet =
+            parse("Time: 7 05\nDistance: 9 040\n".as_bytes()).expect("Unable to read races");
+        let race = fix_kerning(&sheet).expect("Unable to fix kerning");
+        assert_eq!((race.time, race.distance), (705, 9040));
+    }
+
+    #[test]
+    fn closed_form_matches_brute_force() {
+        for time in 0..80u64 {
+            for distance in 0..(time * time / 4 + 3) {
+                let race = Race { time, distance };
+                assert_eq!(
+                    get_num_winners(&race),
+                    get_num_winners_brute_force(&race),
+                    "time {time}, distance {distance}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn closed_form_large_races() {
+        // Holding for exactly half the race ties or beats the record
+        assert_eq!(count_winning_holds(1 << 64, (1 << 126) - 1), 1);
+        assert_eq!(count_winning_holds(1 << 64, 1 << 126), 0);
+        assert_eq!(count_winning_holds(u128::MAX, 0), u128::MAX - 1);
+        assert_eq!(count_winning_holds(u128::MAX, u128::MAX), u128::MAX - 3);
+        assert_eq!(count_winning_holds(71530, 940200), 71503);
+    }
+
+    #[test]
+    fn classic_model_matches_puzzle() {
+        for time in 0..40u64 {
+            for distance in 0..(time * time / 4 + 3) {
+                let race = Race { time, distance };
+                let num_winners = match Classic.winning_holds(&race) {
+                    Some(holds) => holds.end() - holds.start() + 1,
+                    None => 0,
+                };
+                assert_eq!(num_winners, get_num_winners(&race));
+            }
+        }
+        let race = Race {
+            time: 30,
+            distance: 200,
+        };
+        assert_eq!(Classic.winning_holds(&race), Some(11..=19));
+        assert_eq!(Classic.optimal_hold(&race), (15, 225));
+        assert_eq!(Classic.margin(&race), Some(25));
+    }
+
+    #[test]
+    fn other_models() {
+        let race = Race {
+            time: 30,
+            distance: 200,
+        };
+        // h^2 * (30 - h) peaks at h = 20
+        assert_eq!(QuadraticAcceleration.optimal_hold(&race), (20, 4000));
+        assert_eq!(QuadraticAcceleration.winning_holds(&race), Some(3..=29));
+
+        let capped = CappedSpeed { top_speed: 10 };
+        assert_eq!(capped.optimal_hold(&race), (10, 200));
+        assert_eq!(capped.winning_holds(&race), None);
+        assert_eq!(capped.margin(&race), None);
+
+        let penalty = ChargePenalty { penalty: 4 };
+        assert_eq!(penalty.optimal_hold(&race), (13, 169));
+        assert_eq!(penalty.winning_holds(&race), None);
+        let race = Race {
+            time: 30,
+            distance: 160,
+        };
+        assert_eq!(penalty.winning_holds(&race), Some(11..=15));
+        assert_eq!(penalty.margin(&race), Some(9));
+    }
+
+    #[test]
+    fn reports() {
+        let sheet = parse(SAMPLE.as_bytes()).expect("Unable to read races");
+        let reports = report_part1(&sheet.races);
+        assert_eq!(
+            reports[0],
+            RaceReport {
+                num_winners: 4,
+                first_winning_hold: Some(2),
+                last_winning_hold: Some(5),
+                optimal_hold: 3,
+                best_distance: 12,
+                record_headroom: Some(2),
+            }
+        );
+        assert_eq!(reports[2].first_winning_hold, Some(11));
+        assert_eq!(reports[2].last_winning_hold, Some(19));
+
+        let report = report_part2(&sheet).expect("Unable to fix kerning");
+        assert_eq!(report.num_winners, 71503);
+        assert_eq!(report.first_winning_hold, Some(14));
+        assert_eq!(report.last_winning_hold, Some(71516));
+        assert_eq!(report.best_distance, 35765 * 35765);
+
+        let unwinnable = Race {
+            time: 7,
+            distance: 12,
+        };
+        assert_eq!(unwinnable.report().first_winning_hold, None);
+        assert_eq!(unwinnable.report().record_headroom, None);
     }
 }
@@ -0,0 +1,24 @@
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{stdin, Read};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let (flags, paths): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let input: Box<dyn Read> = match paths.first() {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(stdin().lock()),
+    };
+    let sheet = day_6::parse(input)?;
+    println!("Part 1: {}", day_6::solve_part1(&sheet.races));
+    println!("Part 2: {}", day_6::solve_part2(&sheet)?);
+
+    if flags.iter().any(|flag| flag == "--report") {
+        for report in day_6::report_part1(&sheet.races) {
+            println!("{report:?}");
+        }
+        println!("{:?}", day_6::report_part2(&sheet)?);
+    }
+    Ok(())
+}
@@ -1,184 +1,544 @@
-mod hands {
-    use std::cmp::{Ordering};
-    use std::collections::HashMap;
-
-    #[derive(Debug, Ord, Eq, PartialEq, PartialOrd)]
-    enum HandType {
-        FiveOfAKind = 6,
-        FourOfAKind = 5,
-        FullHouse = 4,
-        ThreeOfAKind = 3,
-        TwoPair = 2,
-        OnePair = 1,
-        HighCard = 0,
+pub mod hands {
+    use std::cmp::Ordering;
+    use std::collections::{HashMap, HashSet};
+    use std::error::Error;
+    use std::fmt;
+    use std::io::{BufRead, BufReader, Read};
+    use std::str::FromStr;
+
+    /// Cards per hand in the puzzle.
+    pub const HAND_SIZE: usize = 5;
+
+    #[derive(Debug, Ord, Eq, PartialEq, PartialOrd, Clone, Copy)]
+    pub struct HandType {
+        // Position in the category table, higher is stronger
+        pub rank: usize,
+        pub name: &'static str,
     }
 
-    #[derive(Debug)]
-    pub struct Hand {
-        cards: [u8; 5],
-        hand_type: HandType,
-        pub bid: usize,
+    /// Card count patterns and their names, weakest first. Each pattern is a sorted
+    /// multiset of counts, largest first.
+    pub type CategoryTable = [(&'static [usize], &'static str)];
+
+    pub const STANDARD_CATEGORIES: &CategoryTable = &[
+        (&[1], "HighCard"),
+        (&[2], "OnePair"),
+        (&[2, 2], "TwoPair"),
+        (&[3], "ThreeOfAKind"),
+        (&[3, 2], "FullHouse"),
+        (&[4], "FourOfAKind"),
+        (&[5], "FiveOfAKind"),
+    ];
+
+    /// Strongest category whose pattern fits inside `counts`, so the same table works for
+    /// any hand size. `counts` must be sorted largest first.
+    pub fn classify(table: &CategoryTable, counts: &[usize]) -> HandType {
+        let rank = table
+            .iter()
+            .rposition(|(pattern, _)| {
+                pattern.len() <= counts.len()
+                    && pattern
+                        .iter()
+                        .zip(counts)
+                        .all(|(needed, count)| count >= needed)
+            })
+            .unwrap_or(0);
+        HandType {
+            rank,
+            name: table[rank].1,
+        }
     }
 
-    impl Hand {
-        pub fn from_str(hand_val: &str, bid: usize) -> Self {
-            let card_strengths: [u8; 5] = hand_val
+    // Count of each distinct card, largest first
+    fn sorted_counts(hand: &[u8]) -> Vec<usize> {
+        let mut map: HashMap<u8, usize> = HashMap::new();
+        for card in hand {
+            *map.entry(*card).or_insert(0) += 1;
+        }
+        let mut counts: Vec<usize> = map.into_values().collect();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        counts
+    }
+
+    // Hands up to this size are classified without any heap allocation
+    const MAX_STACK_HAND: usize = 16;
+
+    pub trait RuleSet {
+        /// Strength of a card, used to break ties between hands of the same type.
+        fn card_strength(&self, c: char) -> Option<u8>;
+
+        /// Every card this rule set accepts.
+        fn card_chars(&self) -> &str {
+            "23456789TJQKA"
+        }
+
+        /// Whether cards of this strength can stand in for any other card.
+        fn is_wild(&self, _strength: u8) -> bool {
+            false
+        }
+
+        fn categories(&self) -> &CategoryTable {
+            STANDARD_CATEGORIES
+        }
+
+        fn card_char(&self, strength: u8) -> Option<char> {
+            self.card_chars()
                 .chars()
-                .map(Hand::char_to_strength)
-                .collect::<Vec<u8>>()
-                .try_into()
-                .expect("Invalid hand");
-            Hand {
-                cards: card_strengths,
-                hand_type: Hand::get_hand_type(card_strengths),
-                bid,
+                .find(|c| self.card_strength(*c) == Some(strength))
+        }
+
+        /// Type of the best hand the wild cards can make.
+        fn hand_type(&self, hand: &[u8]) -> HandType {
+            // Piling every wild card onto the most common card is only guaranteed best for
+            // the standard table, other tables need the full search
+            if hand.len() > MAX_STACK_HAND || self.categories() != STANDARD_CATEGORIES {
+                return self.best_substitution(hand).hand_type;
+            }
+            // Same as best_substitution, but without allocating
+            let mut seen = [0u8; MAX_STACK_HAND];
+            let mut counts = [0usize; MAX_STACK_HAND];
+            let mut distinct = 0;
+            let mut num_wild = 0;
+            for card in hand {
+                if self.is_wild(*card) {
+                    num_wild += 1;
+                    continue;
+                }
+                match seen[..distinct].iter().position(|seen| seen == card) {
+                    Some(i) => counts[i] += 1,
+                    None => {
+                        seen[distinct] = *card;
+                        counts[distinct] = 1;
+                        distinct += 1;
+                    }
+                }
             }
+            let counts = &mut counts[..distinct.max(1)];
+            counts.sort_unstable_by(|a, b| b.cmp(a));
+            counts[0] += num_wild;
+            classify(self.categories(), counts)
         }
 
-        pub fn from_str_with_jokers(hand_val: &str, bid: usize) -> Self {
-            let card_strengths: [u8; 5] = hand_val
+        /// The best concrete hand the wild cards can make. Searches every way of spreading
+        /// them over the cards already held or cards not in the hand yet, trying the most
+        /// common and strongest cards first so ties read naturally.
+        fn best_substitution(&self, hand: &[u8]) -> Substitution {
+            let mut map: HashMap<u8, usize> = HashMap::new();
+            for card in hand.iter().filter(|card| !self.is_wild(**card)) {
+                *map.entry(*card).or_insert(0) += 1;
+            }
+            let mut groups: Vec<(u8, usize)> = map.into_iter().collect();
+            groups.sort_unstable_by_key(|(card, count)| std::cmp::Reverse((*count, *card)));
+            // Cards a wild card could become without joining a group, strongest first
+            let mut unused: Vec<u8> = self
+                .card_chars()
                 .chars()
-                .map(Hand::char_to_strength_joker)
-                .collect::<Vec<u8>>()
-                .try_into()
-                .expect("Invalid hand");
-            let num_jokers = card_strengths.iter().filter(|num| **num == 0).count();
-            let hand_type = Hand::get_hand_type_modified(card_strengths, num_jokers);
-            Hand {
-                cards: card_strengths,
+                .filter_map(|c| self.card_strength(c))
+                .filter(|strength| !self.is_wild(*strength))
+                .filter(|strength| groups.iter().all(|(card, _)| card != strength))
+                .collect();
+            unused.sort_unstable_by(|a, b| b.cmp(a));
+            let num_wild = hand.len() - groups.iter().map(|(_, count)| count).sum::<usize>();
+
+            let mut search = WildSearch {
+                categories: self.categories(),
+                held: groups.len(),
+                unused,
+                seen: HashSet::new(),
+                best: None,
+            };
+            search.place(&mut groups.clone(), num_wild);
+            let (hand_type, best_groups) = search.best.expect("The search always tries something");
+
+            // One target per wild card, handed out in order to whichever groups grew
+            let mut targets = best_groups
+                .iter()
+                .enumerate()
+                .flat_map(|(i, (card, count))| {
+                    let original = groups.get(i).map_or(0, |(_, count)| *count);
+                    std::iter::repeat_n(*card, count - original)
+                });
+            let cards: Vec<u8> = hand
+                .iter()
+                .map(|card| match self.is_wild(*card) {
+                    // With nothing at all to copy, a wild card stays as it is
+                    true => targets.next().unwrap_or(*card),
+                    false => *card,
+                })
+                .collect();
+            Substitution {
+                original: hand.to_vec(),
+                cards,
                 hand_type,
-                bid
             }
         }
+    }
 
-        fn get_hand_type_modified(hand: [u8; 5], num_jokers: usize) -> HandType{
-            let mut map: HashMap<u8, usize> = HashMap::new();
-            let mut max = 0;
-            let mut max_card: Option<u8> = None;
-            for card in hand {
-                // Only count non-jokers
-                if card != 0 {
-                    let new_count = match map.get(&card) {
-                        Some(count) => count + 1,
-                        None => 1
-                    };
-                    if new_count > max {
-                        max = new_count;
-                        max_card = Some(card);
-                    }
-                    map.insert(card, new_count);
+    // Depth-first search over where each wild card goes, skipping count patterns already tried
+    struct WildSearch<'a> {
+        categories: &'a CategoryTable,
+        // Groups before this index came from the hand, the rest from `unused`
+        held: usize,
+        unused: Vec<u8>,
+        seen: HashSet<(Vec<usize>, usize)>,
+        best: Option<(HandType, Vec<(u8, usize)>)>,
+    }
+
+    impl WildSearch<'_> {
+        fn place(&mut self, groups: &mut Vec<(u8, usize)>, num_wild: usize) {
+            let mut counts: Vec<usize> = groups.iter().map(|(_, count)| *count).collect();
+            counts.sort_unstable_by(|a, b| b.cmp(a));
+            let new_card = self.unused.get(groups.len() - self.held).copied();
+            if num_wild == 0 || (groups.is_empty() && new_card.is_none()) {
+                let hand_type = classify(self.categories, &counts);
+                if self.best.as_ref().is_none_or(|(best, _)| hand_type > *best) {
+                    self.best = Some((hand_type, groups.clone()));
                 }
+                return;
+            }
+            if !self.seen.insert((counts, num_wild)) {
+                return;
             }
+            for i in 0..groups.len() {
+                groups[i].1 += 1;
+                self.place(groups, num_wild - 1);
+                groups[i].1 -= 1;
+            }
+            if let Some(card) = new_card {
+                groups.push((card, 1));
+                self.place(groups, num_wild - 1);
+                groups.pop();
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub struct Substitution {
+        pub original: Vec<u8>,
+        // original with every wild card replaced
+        pub cards: Vec<u8>,
+        pub hand_type: HandType,
+    }
 
-            // Increment top card count by joker count
-            if let Some(card) = max_card {
-                map.insert(card, *map.get(&card).unwrap() + num_jokers);
+    impl Substitution {
+        pub fn describe(&self, rules: &impl RuleSet) -> String {
+            let to_string = |cards: &[u8]| -> String {
+                cards
+                    .iter()
+                    .map(|card| rules.card_char(*card).unwrap_or('?'))
+                    .collect()
+            };
+            let replaced: Vec<String> = self
+                .original
+                .iter()
+                .zip(&self.cards)
+                .enumerate()
+                .filter(|(_, (original, card))| original != card)
+                .map(|(i, (original, card))| {
+                    format!(
+                        "{} at {} as {}",
+                        rules.card_char(*original).unwrap_or('?'),
+                        i,
+                        rules.card_char(*card).unwrap_or('?')
+                    )
+                })
+                .collect();
+            if replaced.is_empty() {
+                format!("{} is {}", to_string(&self.original), self.hand_type.name)
+            } else {
+                format!(
+                    "{} becomes {} ({}), {}",
+                    to_string(&self.original),
+                    to_string(&self.cards),
+                    replaced.join(", "),
+                    self.hand_type.name
+                )
             }
+        }
+    }
 
-            match map.len() {
-                // 5 of a kind or all jokers
-                0 | 1 => HandType::FiveOfAKind,
-                // 4 of a kind or full house
-                2 => {
-                    for val in map.values() {
-                        if *val == 4 {
-                            return HandType::FourOfAKind;
-                        }
-                    }
-                    HandType::FullHouse
+    /// Tries every way of replacing the wild cards, for checking `RuleSet::best_substitution`.
+    pub fn brute_force_substitution(rules: &impl RuleSet, hand: &[u8]) -> Substitution {
+        let options: Vec<u8> = rules
+            .card_chars()
+            .chars()
+            .filter_map(|c| rules.card_strength(c))
+            .filter(|strength| !rules.is_wild(*strength))
+            .collect();
+        let wild_positions: Vec<usize> = (0..hand.len())
+            .filter(|i| rules.is_wild(hand[*i]))
+            .collect();
+        let mut best: Option<Substitution> = None;
+        let mut choice = vec![0usize; wild_positions.len()];
+        loop {
+            let mut cards = hand.to_vec();
+            for (position, option) in wild_positions.iter().zip(&choice) {
+                if let Some(card) = options.get(*option) {
+                    cards[*position] = *card;
                 }
-                // 3 of a kind or two pair
-                3 => {
-                    for val in map.values() {
-                        if *val == 3 {
-                            return HandType::ThreeOfAKind;
-                        }
-                    }
-                    HandType::TwoPair
-                }
-                // One pair
-                4 => HandType::OnePair,
-                // High card
-                5 => HandType::HighCard,
-                _ => panic!("Invalid hand type"),
             }
+            let hand_type = classify(rules.categories(), &sorted_counts(&cards));
+            if best.as_ref().is_none_or(|best| hand_type > best.hand_type) {
+                best = Some(Substitution {
+                    original: hand.to_vec(),
+                    cards,
+                    hand_type,
+                });
+            }
+
+            // Advance to the next combination, like an odometer
+            let mut i = 0;
+            while i < choice.len() && choice[i] + 1 >= options.len() {
+                choice[i] = 0;
+                i += 1;
+            }
+            if i == choice.len() {
+                break;
+            }
+            choice[i] += 1;
         }
+        best.expect("At least one substitution is always tried")
+    }
 
-        fn get_hand_type(hand: [u8; 5]) -> HandType {
-            let mut map: HashMap<u8, u8> = HashMap::new();
-            for card in hand {
-                match map.get(&card) {
-                    Some(count) => map.insert(card, count + 1),
-                    None => map.insert(card, 1),
-                };
+    /// Part 1 rules.
+    pub struct Standard;
+
+    impl RuleSet for Standard {
+        fn card_strength(&self, c: char) -> Option<u8> {
+            match c {
+                '2' => Some(0),
+                '3' => Some(1),
+                '4' => Some(2),
+                '5' => Some(3),
+                '6' => Some(4),
+                '7' => Some(5),
+                '8' => Some(6),
+                '9' => Some(7),
+                'T' => Some(8),
+                'J' => Some(9),
+                'Q' => Some(10),
+                'K' => Some(11),
+                'A' => Some(12),
+                _ => None,
+            }
+        }
+    }
+
+    /// Part 2 rules, where J is the weakest card but acts as a wild card.
+    pub struct Jokers;
+
+    impl RuleSet for Jokers {
+        fn card_strength(&self, c: char) -> Option<u8> {
+            match c {
+                'J' => Some(0),
+                '2' => Some(1),
+                '3' => Some(2),
+                '4' => Some(3),
+                '5' => Some(4),
+                '6' => Some(5),
+                '7' => Some(6),
+                '8' => Some(7),
+                '9' => Some(8),
+                'T' => Some(9),
+                'Q' => Some(10),
+                'K' => Some(11),
+                'A' => Some(12),
+                _ => None,
             }
+        }
 
-            match map.len() {
-                // 5 of a kind
-                1 => HandType::FiveOfAKind,
-                // 4 of a kind or full house
-                2 => {
-                    for val in map.values() {
-                        if *val == 4 {
-                            return HandType::FourOfAKind;
-                        }
-                    }
-                    HandType::FullHouse
+        fn is_wild(&self, strength: u8) -> bool {
+            strength == 0
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Hand {
+        cards: Vec<u8>,
+        hand_type: HandType,
+        pub bid: usize,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub enum ParseHandError {
+        WrongLength {
+            expected: usize,
+            found: usize,
+        },
+        UnknownCard {
+            card: char,
+            index: usize,
+        },
+        MissingBid,
+        BadBid(String),
+        Unreadable(String),
+        /// A poker card with a rank but no suit, at the position the suit should be.
+        MissingSuit {
+            index: usize,
+        },
+        /// The same rank and suit dealt twice, e.g. "Ah".
+        DuplicateCard(String),
+    }
+
+    impl fmt::Display for ParseHandError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ParseHandError::WrongLength { expected, found } => {
+                    write!(f, "expected {expected} cards but found {found}")
                 }
-                // 3 of a kind or two pair
-                3 => {
-                    for val in map.values() {
-                        if *val == 3 {
-                            return HandType::ThreeOfAKind;
-                        }
-                    }
-                    HandType::TwoPair
+                ParseHandError::UnknownCard { card, index } => {
+                    write!(f, "unknown card '{card}' at position {index}")
                 }
-                // One pair
-                4 => HandType::OnePair,
-                // High card
-                5 => HandType::HighCard,
-                _ => panic!("Invalid hand type"),
+                ParseHandError::MissingBid => write!(f, "missing bid"),
+                ParseHandError::BadBid(bid) => write!(f, "invalid bid '{bid}'"),
+                ParseHandError::Unreadable(reason) => write!(f, "unable to read line: {reason}"),
+                ParseHandError::MissingSuit { index } => {
+                    write!(f, "missing suit at position {index}")
+                }
+                ParseHandError::DuplicateCard(card) => write!(f, "{card} appears more than once"),
             }
         }
+    }
 
-        fn char_to_strength(c: char) -> u8 {
-            match c {
-                '2' => 0,
-                '3' => 1,
-                '4' => 2,
-                '5' => 3,
-                '6' => 4,
-                '7' => 5,
-                '8' => 6,
-                '9' => 7,
-                'T' => 8,
-                'J' => 9,
-                'Q' => 10,
-                'K' => 11,
-                'A' => 12,
-                _ => panic!("Invalid card"),
-            }
-        }
-
-        fn char_to_strength_joker(c: char) -> u8 {
-            match c {
-                'J' => 0,
-                '2' => 1,
-                '3' => 2,
-                '4' => 3,
-                '5' => 4,
-                '6' => 5,
-                '7' => 6,
-                '8' => 7,
-                '9' => 8,
-                'T' => 9,
-                'Q' => 10,
-                'K' => 11,
-                'A' => 12,
-                _ => panic!("Invalid card"),
+    impl Error for ParseHandError {}
+
+    #[derive(Debug, PartialEq)]
+    pub struct LineError {
+        // Counting from 1
+        pub line: usize,
+        pub error: ParseHandError,
+    }
+
+    impl fmt::Display for LineError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "line {}: {}", self.line, self.error)
+        }
+    }
+
+    /// Every bad line in an input, not just the first.
+    #[derive(Debug, PartialEq)]
+    pub struct ParseErrors(pub Vec<LineError>);
+
+    impl fmt::Display for ParseErrors {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let lines: Vec<String> = self.0.iter().map(|error| error.to_string()).collect();
+            write!(f, "{}", lines.join("\n"))
+        }
+    }
+
+    impl Error for ParseErrors {}
+
+    /// Splits a line like "32T3K 765" into the cards and the bid.
+    pub fn split_line(line: &str) -> Result<(&str, usize), ParseHandError> {
+        let mut segments = line.split_whitespace();
+        let hand_val = segments.next().unwrap_or("");
+        let bid_val = segments.next().ok_or(ParseHandError::MissingBid)?;
+        let bid = bid_val
+            .parse()
+            .map_err(|_| ParseHandError::BadBid(bid_val.to_string()))?;
+        Ok((hand_val, bid))
+    }
+
+    fn check_length(hand_val: &str, hand_size: usize) -> Result<(), ParseHandError> {
+        let found = hand_val.chars().count();
+        if found != hand_size {
+            return Err(ParseHandError::WrongLength {
+                expected: hand_size,
+                found,
+            });
+        }
+        Ok(())
+    }
+
+    // Parses every line, collecting every bad line rather than stopping at the first
+    fn parse_lines<T>(
+        input: impl Read,
+        parse_line: impl Fn(&str) -> Result<T, ParseHandError>,
+    ) -> Result<Vec<T>, ParseErrors> {
+        let mut parsed = Vec::new();
+        let mut errors = Vec::new();
+        for (i, line) in BufReader::new(input).lines().enumerate() {
+            let result = line
+                .map_err(|err| ParseHandError::Unreadable(err.to_string()))
+                .and_then(|line| parse_line(&line));
+            match result {
+                Ok(value) => parsed.push(value),
+                Err(error) => errors.push(LineError { line: i + 1, error }),
             }
         }
+        if errors.is_empty() {
+            Ok(parsed)
+        } else {
+            Err(ParseErrors(errors))
+        }
+    }
+
+    /// Reads one hand per line, collecting every bad line.
+    pub fn parse_hands(
+        input: impl Read,
+        rules: &impl RuleSet,
+        hand_size: usize,
+    ) -> Result<Vec<Hand>, ParseErrors> {
+        parse_lines(input, |line| Hand::parse_line(line, rules, hand_size))
+    }
+
+    impl Hand {
+        pub fn try_new(
+            hand_val: &str,
+            bid: usize,
+            rules: &impl RuleSet,
+        ) -> Result<Self, ParseHandError> {
+            let card_strengths: Vec<u8> = hand_val
+                .chars()
+                .enumerate()
+                .map(|(index, card)| {
+                    rules
+                        .card_strength(card)
+                        .ok_or(ParseHandError::UnknownCard { card, index })
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(Hand {
+                hand_type: rules.hand_type(&card_strengths),
+                cards: card_strengths,
+                bid,
+            })
+        }
+
+        /// Parses a line like "32T3K 765", which must have exactly `hand_size` cards.
+        pub fn parse_line(
+            line: &str,
+            rules: &impl RuleSet,
+            hand_size: usize,
+        ) -> Result<Self, ParseHandError> {
+            let (hand_val, bid) = split_line(line)?;
+            check_length(hand_val, hand_size)?;
+            Hand::try_new(hand_val, bid, rules)
+        }
+
+        pub fn hand_type(&self) -> HandType {
+            self.hand_type
+        }
+
+        pub fn cards(&self) -> &[u8] {
+            &self.cards
+        }
+    }
+
+    /// A puzzle line under the part 1 rules.
+    impl FromStr for Hand {
+        type Err = ParseHandError;
+
+        fn from_str(line: &str) -> Result<Self, Self::Err> {
+            Hand::parse_line(line, &Standard, HAND_SIZE)
+        }
+    }
+
+    impl TryFrom<&str> for Hand {
+        type Error = ParseHandError;
+
+        fn try_from(line: &str) -> Result<Self, Self::Error> {
+            line.parse()
+        }
     }
 
     impl Eq for Hand {}
@@ -187,83 +547,1100 @@ mod hands {
             self.cards == other.cards && self.hand_type == other.hand_type
         }
     }
-    impl Ord for Hand {
-        fn cmp(&self, other: &Self) -> Ordering {
-            match self.hand_type.cmp(&other.hand_type) {
-                Ordering::Less => Ordering::Less,
-                Ordering::Greater => Ordering::Greater,
-                Ordering::Equal => {
-                    // If equal hand types, iterate over values
-                    for i in 0..self.cards.len() {
-                        match self.cards[i].cmp(&other.cards[i]) {
-                            Ordering::Less => return Ordering::Less,
-                            Ordering::Greater => return Ordering::Greater,
-                            Ordering::Equal => {}
-                        }
+    // Compare by type first, then card by card
+    fn compare_hands<T: Ord>(type_a: &T, cards_a: &[u8], type_b: &T, cards_b: &[u8]) -> Ordering {
+        match type_a.cmp(type_b) {
+            Ordering::Less => Ordering::Less,
+            Ordering::Greater => Ordering::Greater,
+            Ordering::Equal => {
+                // If equal hand types, iterate over values
+                for (a, b) in cards_a.iter().zip(cards_b) {
+                    match a.cmp(b) {
+                        Ordering::Less => return Ordering::Less,
+                        Ordering::Greater => return Ordering::Greater,
+                        Ordering::Equal => {}
                     }
-                    Ordering::Equal
                 }
+                cards_a.len().cmp(&cards_b.len())
             }
         }
     }
+
+    impl Ord for Hand {
+        fn cmp(&self, other: &Self) -> Ordering {
+            compare_hands(&self.hand_type, &self.cards, &other.hand_type, &other.cards)
+        }
+    }
     impl PartialOrd<Self> for Hand {
         fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
             Some(self.cmp(other))
         }
     }
+
+    #[derive(Debug, Ord, Eq, PartialEq, PartialOrd, Clone, Copy)]
+    pub enum PokerCategory {
+        StraightFlush = 8,
+        FourOfAKind = 7,
+        FullHouse = 6,
+        Flush = 5,
+        Straight = 4,
+        ThreeOfAKind = 3,
+        TwoPair = 2,
+        OnePair = 1,
+        HighCard = 0,
+    }
+
+    /// A regular five-card poker hand, written as rank then suit, e.g. "AhKd5c5s2h".
+    #[derive(Debug)]
+    pub struct PokerHand {
+        category: PokerCategory,
+        // Card strengths in tie-break order
+        ranks: [u8; 5],
+        pub bid: usize,
+    }
+
+    impl PokerHand {
+        pub fn new(hand_val: &str, bid: usize) -> Result<Self, ParseHandError> {
+            let chars: Vec<char> = hand_val.chars().collect();
+            let mut cards: Vec<(u8, char)> = Vec::with_capacity(HAND_SIZE);
+            for (index, card) in chars.chunks(2).enumerate() {
+                let [rank, suit] = card else {
+                    return Err(ParseHandError::MissingSuit {
+                        index: 2 * index + 1,
+                    });
+                };
+                let strength =
+                    Standard
+                        .card_strength(*rank)
+                        .ok_or(ParseHandError::UnknownCard {
+                            card: *rank,
+                            index: 2 * index,
+                        })?;
+                if !"cdhs".contains(*suit) {
+                    return Err(ParseHandError::UnknownCard {
+                        card: *suit,
+                        index: 2 * index + 1,
+                    });
+                }
+                if cards.contains(&(strength, *suit)) {
+                    return Err(ParseHandError::DuplicateCard(format!("{rank}{suit}")));
+                }
+                cards.push((strength, *suit));
+            }
+            let found = cards.len();
+            let cards: [(u8, char); HAND_SIZE] =
+                cards.try_into().map_err(|_| ParseHandError::WrongLength {
+                    expected: HAND_SIZE,
+                    found,
+                })?;
+            let (category, ranks) = PokerHand::evaluate(&cards);
+            Ok(PokerHand {
+                category,
+                ranks,
+                bid,
+            })
+        }
+
+        /// Parses a line like "AhKd5c5s2h 765".
+        pub fn parse_line(line: &str) -> Result<Self, ParseHandError> {
+            let (hand_val, bid) = split_line(line)?;
+            PokerHand::new(hand_val, bid)
+        }
+
+        pub fn category(&self) -> PokerCategory {
+            self.category
+        }
+
+        fn evaluate(cards: &[(u8, char); 5]) -> (PokerCategory, [u8; 5]) {
+            let mut map: HashMap<u8, usize> = HashMap::new();
+            for (rank, _) in cards {
+                *map.entry(*rank).or_insert(0) += 1;
+            }
+            // Bigger groups first, then higher ranks
+            let mut groups: Vec<(usize, u8)> = map.into_iter().map(|(r, c)| (c, r)).collect();
+            groups.sort_unstable_by(|a, b| b.cmp(a));
+            let mut ranks = [0u8; 5];
+            let mut i = 0;
+            for (count, rank) in &groups {
+                for _ in 0..*count {
+                    ranks[i] = *rank;
+                    i += 1;
+                }
+            }
+
+            let is_flush = cards.iter().all(|(_, suit)| *suit == cards[0].1);
+            let is_straight = groups.len() == 5 && (ranks[0] - ranks[4] == 4 || ranks == WHEEL);
+            if is_straight && ranks == WHEEL {
+                // The ace plays low in A-2-3-4-5
+                ranks = [3, 2, 1, 0, 12];
+            }
+
+            let counts: Vec<usize> = groups.iter().map(|(count, _)| *count).collect();
+            let category = match (is_straight, is_flush, counts.as_slice()) {
+                (true, true, _) => PokerCategory::StraightFlush,
+                (_, _, [4, ..]) => PokerCategory::FourOfAKind,
+                (_, _, [3, 2]) => PokerCategory::FullHouse,
+                (_, true, _) => PokerCategory::Flush,
+                (true, _, _) => PokerCategory::Straight,
+                (_, _, [3, ..]) => PokerCategory::ThreeOfAKind,
+                (_, _, [2, 2, ..]) => PokerCategory::TwoPair,
+                (_, _, [2, ..]) => PokerCategory::OnePair,
+                _ => PokerCategory::HighCard,
+            };
+            (category, ranks)
+        }
+    }
+
+    impl FromStr for PokerHand {
+        type Err = ParseHandError;
+
+        fn from_str(line: &str) -> Result<Self, Self::Err> {
+            PokerHand::parse_line(line)
+        }
+    }
+
+    /// Reads one poker hand per line, collecting every bad line.
+    pub fn parse_poker_hands(input: impl Read) -> Result<Vec<PokerHand>, ParseErrors> {
+        parse_lines(input, PokerHand::parse_line)
+    }
+
+    // A, 5, 4, 3, 2 as sorted by evaluate
+    const WHEEL: [u8; 5] = [12, 3, 2, 1, 0];
+
+    impl Eq for PokerHand {}
+    impl PartialEq<Self> for PokerHand {
+        fn eq(&self, other: &Self) -> bool {
+            self.ranks == other.ranks && self.category == other.category
+        }
+    }
+    impl Ord for PokerHand {
+        fn cmp(&self, other: &Self) -> Ordering {
+            compare_hands(&self.category, &self.ranks, &other.category, &other.ranks)
+        }
+    }
+    impl PartialOrd<Self> for PokerHand {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+}
+
+pub mod equity {
+    use crate::hands::{Hand, ParseHandError, RuleSet};
+    use std::cmp::Ordering;
+    use std::error::Error;
+    use std::thread;
+
+    // Trials are run in fixed-size chunks with their own seeds, so the result for a given
+    // seed doesn't depend on how many threads share the work
+    const CHUNK_SIZE: usize = 1024;
+
+    pub struct EquityQuery {
+        /// Cards already in our hand.
+        pub partial: String,
+        /// Cards left to deal from, one char per card.
+        pub deck: String,
+        pub num_opponents: usize,
+        pub hand_size: usize,
+        pub trials: usize,
+        pub seed: u64,
+        pub threads: usize,
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    pub struct Equity {
+        pub wins: usize,
+        pub ties: usize,
+        pub losses: usize,
+    }
+
+    impl Equity {
+        pub fn win_probability(&self) -> f64 {
+            self.wins as f64 / self.trials() as f64
+        }
+
+        pub fn tie_probability(&self) -> f64 {
+            self.ties as f64 / self.trials() as f64
+        }
+
+        pub fn lose_probability(&self) -> f64 {
+            self.losses as f64 / self.trials() as f64
+        }
+
+        fn trials(&self) -> usize {
+            self.wins + self.ties + self.losses
+        }
+    }
+
+    // SplitMix64, which is plenty for shuffling and needs no dependencies
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+            z ^ (z >> 31)
+        }
+
+        // Uniform in 0..n. Plain modulo would favour small values, so draws from the
+        // incomplete block at the top of the range are thrown away
+        fn below(&mut self, n: usize) -> usize {
+            let n = n as u64;
+            let limit = u64::MAX - u64::MAX % n;
+            loop {
+                let value = self.next();
+                if value < limit {
+                    return (value % n) as usize;
+                }
+            }
+        }
+    }
+
+    impl EquityQuery {
+        /// Deals out the rest of our hand and every opponent's hand at random, `trials` times.
+        pub fn estimate(&self, rules: &(impl RuleSet + Sync)) -> Result<Equity, Box<dyn Error>> {
+            if self.trials == 0 {
+                return Err("Need at least one trial".into());
+            }
+            let partial: Vec<char> = self.partial.chars().collect();
+            let deck: Vec<char> = self.deck.chars().collect();
+            if partial.len() > self.hand_size {
+                return Err("Partial hand has too many cards".into());
+            }
+            if let Some(c) = partial
+                .iter()
+                .chain(&deck)
+                .find(|c| rules.card_strength(**c).is_none())
+            {
+                return Err(format!("Invalid card {c}").into());
+            }
+            let needed = self
+                .num_opponents
+                .checked_mul(self.hand_size)
+                .and_then(|dealt| dealt.checked_add(self.hand_size - partial.len()));
+            if needed.is_none_or(|needed| needed > deck.len()) {
+                return Err("Not enough cards left in the deck".into());
+            }
+
+            let num_chunks = self.trials.div_ceil(CHUNK_SIZE);
+            let threads = self.threads.clamp(1, num_chunks.max(1));
+            let results: Vec<Result<Equity, ParseHandError>> = thread::scope(|scope| {
+                let handles: Vec<_> = (0..threads)
+                    .map(|thread_index| {
+                        let (partial, deck) = (&partial, &deck);
+                        scope.spawn(move || {
+                            let mut equity = Equity::default();
+                            for chunk in (thread_index..num_chunks).step_by(threads) {
+                                let trials = CHUNK_SIZE.min(self.trials - chunk * CHUNK_SIZE);
+                                let mut rng = Rng(
+                                    self.seed ^ (chunk as u64).wrapping_mul(0xA24BAED4963EE407)
+                                );
+                                for _ in 0..trials {
+                                    self.run_trial(rules, partial, deck, &mut rng, &mut equity)?;
+                                }
+                            }
+                            Ok(equity)
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().expect("Simulation thread panicked"))
+                    .collect()
+            });
+
+            let mut total = Equity::default();
+            for equity in results {
+                let equity = equity?;
+                total.wins += equity.wins;
+                total.ties += equity.ties;
+                total.losses += equity.losses;
+            }
+            Ok(total)
+        }
+
+        fn run_trial(
+            &self,
+            rules: &impl RuleSet,
+            partial: &[char],
+            deck: &[char],
+            rng: &mut Rng,
+            equity: &mut Equity,
+        ) -> Result<(), ParseHandError> {
+            // Partial Fisher-Yates, only shuffling as many cards as get dealt
+            let mut deck = deck.to_vec();
+            let mut dealt = 0;
+            let mut deal = |n: usize| -> String {
+                for i in dealt..dealt + n {
+                    let j = i + rng.below(deck.len() - i);
+                    deck.swap(i, j);
+                }
+                dealt += n;
+                deck[dealt - n..dealt].iter().collect()
+            };
+
+            let mut hand_val: String = partial.iter().collect();
+            hand_val.push_str(&deal(self.hand_size - partial.len()));
+            let ours = Hand::try_new(&hand_val, 0, rules)?;
+            let mut result = Ordering::Greater;
+            for _ in 0..self.num_opponents {
+                let theirs = Hand::try_new(&deal(self.hand_size), 0, rules)?;
+                result = result.min(ours.cmp(&theirs));
+            }
+            match result {
+                Ordering::Greater => equity.wins += 1,
+                Ordering::Equal => equity.ties += 1,
+                Ordering::Less => equity.losses += 1,
+            }
+            Ok(())
+        }
+    }
+}
+
+pub mod ranking {
+    use crate::hands::{Hand, RuleSet};
+    use std::ops::RangeInclusive;
+
+    // One nibble per card below the four type bits
+    const MAX_PACKED_CARDS: usize = 15;
+
+    /// Packs a hand into one integer that sorts the same way as `Hand`: the type in the top
+    /// four bits, then each card strength plus one in turn. The zero nibbles after a short
+    /// hand sort below every card, so a hand sorts before any longer hand it starts. None if
+    /// the hand is too big to fit or has a card the rules don't know.
+    pub fn sort_key(hand_val: &str, rules: &impl RuleSet) -> Option<u64> {
+        let mut cards = [0u8; MAX_PACKED_CARDS];
+        let mut len = 0;
+        for c in hand_val.chars() {
+            if len == MAX_PACKED_CARDS {
+                return None;
+            }
+            cards[len] = rules.card_strength(c)?;
+            len += 1;
+        }
+        pack(rules.hand_type(&cards[..len]).rank, &cards[..len])
+    }
+
+    fn pack(rank: usize, cards: &[u8]) -> Option<u64> {
+        if rank > 0xF || cards.len() > MAX_PACKED_CARDS || cards.iter().any(|card| *card >= 0xF) {
+            return None;
+        }
+        let mut key = (rank as u64) << 60;
+        for (i, card) in cards.iter().enumerate() {
+            key |= (*card as u64 + 1) << (56 - 4 * i);
+        }
+        Some(key)
+    }
+
+    /// Stable LSD radix sort on the key, a byte at a time.
+    pub fn radix_sort(items: &mut Vec<(u64, usize)>) {
+        let mut buffer = vec![(0u64, 0usize); items.len()];
+        for shift in (0..64).step_by(8) {
+            let mut counts = [0usize; 256];
+            for (key, _) in items.iter() {
+                counts[((key >> shift) & 0xFF) as usize] += 1;
+            }
+            // Every key shares this byte, so the pass would change nothing
+            if counts.contains(&items.len()) {
+                continue;
+            }
+            let mut offsets = [0usize; 256];
+            for i in 1..256 {
+                offsets[i] = offsets[i - 1] + counts[i - 1];
+            }
+            for item in items.iter() {
+                let byte = ((item.0 >> shift) & 0xFF) as usize;
+                buffer[offsets[byte]] = *item;
+                offsets[byte] += 1;
+            }
+            std::mem::swap(items, &mut buffer);
+        }
+    }
+
+    /// Same total as `total_winnings`, via packed keys and a radix sort. Falls back to
+    /// `total_winnings` for hands too big to pack.
+    pub fn total_winnings_radix(hands: &[Hand]) -> usize {
+        let keyed: Option<Vec<(u64, usize)>> = hands
+            .iter()
+            .map(|hand| pack(hand.hand_type().rank, hand.cards()).map(|key| (key, hand.bid)))
+            .collect();
+        let Some(mut keyed) = keyed else {
+            return crate::total_winnings(hands);
+        };
+        radix_sort(&mut keyed);
+        keyed
+            .iter()
+            .enumerate()
+            .map(|(i, (_, bid))| (i + 1) * bid)
+            .sum()
+    }
+
+    /// How to rank hands that are exactly the same.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum TiePolicy {
+        /// Every tied hand gets the average of the ranks they cover.
+        SharedAverage,
+        /// Earlier lines rank lower, which is what a stable sort does.
+        InputOrder,
+        /// Smaller bids rank lower.
+        BidOrder,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub struct Tie {
+        // Line indices of the tied hands, in input order
+        pub indices: Vec<usize>,
+        pub ranks: RangeInclusive<usize>,
+    }
+
+    #[derive(Debug)]
+    pub struct Ranking {
+        /// Twice the total, which is always a whole number even when tied hands share half
+        /// a rank.
+        pub doubled_total_winnings: usize,
+        pub ties: Vec<Tie>,
+    }
+
+    impl Ranking {
+        /// The exact total, or None if shared ranks left half a point over.
+        pub fn total_winnings(&self) -> Option<usize> {
+            self.doubled_total_winnings
+                .is_multiple_of(2)
+                .then_some(self.doubled_total_winnings / 2)
+        }
+
+        /// The total for display, which loses precision past 2^53.
+        pub fn total_winnings_f64(&self) -> f64 {
+            self.doubled_total_winnings as f64 / 2.0
+        }
+    }
+
+    pub fn rank_hands(hands: &[Hand], policy: TiePolicy) -> Ranking {
+        let mut hands: Vec<(&Hand, usize)> = hands.iter().zip(0..).collect();
+        hands.sort();
+
+        // Kept doubled so shared average ranks stay whole numbers
+        let mut doubled_total = 0;
+        let mut ties = Vec::new();
+        let mut start = 0;
+        while start < hands.len() {
+            let mut end = start + 1;
+            while end < hands.len() && hands[end].0 == hands[start].0 {
+                end += 1;
+            }
+            let group = &mut hands[start..end];
+            let (first_rank, last_rank) = (start + 1, end);
+            if group.len() > 1 {
+                ties.push(Tie {
+                    indices: group.iter().map(|(_, i)| *i).collect(),
+                    ranks: first_rank..=last_rank,
+                });
+            }
+            match policy {
+                TiePolicy::SharedAverage => {
+                    for (hand, _) in group.iter() {
+                        doubled_total += hand.bid * (first_rank + last_rank);
+                    }
+                }
+                TiePolicy::InputOrder | TiePolicy::BidOrder => {
+                    if policy == TiePolicy::BidOrder {
+                        group.sort_by_key(|(hand, _)| hand.bid);
+                    }
+                    for (i, (hand, _)) in group.iter().enumerate() {
+                        doubled_total += hand.bid * (first_rank + i) * 2;
+                    }
+                }
+            }
+            start = end;
+        }
+        Ranking {
+            doubled_total_winnings: doubled_total,
+            ties,
+        }
+    }
+}
+
+use hands::{parse_hands, Hand, Jokers, PokerHand, Standard, HAND_SIZE};
+use std::error::Error;
+use std::io::Read;
+
+/// The puzzle's hands, read once under each part's rules.
+#[derive(Debug)]
+pub struct Game {
+    pub standard: Vec<Hand>,
+    pub jokers: Vec<Hand>,
+}
+
+pub fn parse(input: impl Read) -> Result<Game, Box<dyn Error>> {
+    parse_sized(input, HAND_SIZE)
+}
+
+/// Like `parse`, for hands of `hand_size` cards. Every bad line is reported at once.
+pub fn parse_sized(mut input: impl Read, hand_size: usize) -> Result<Game, Box<dyn Error>> {
+    let mut text = String::new();
+    input.read_to_string(&mut text)?;
+    // Both rule sets know the same cards, so the second pass can't find anything new
+    Ok(Game {
+        standard: parse_hands(text.as_bytes(), &Standard, hand_size)?,
+        jokers: parse_hands(text.as_bytes(), &Jokers, hand_size)?,
+    })
+}
+
+pub fn total_winnings(hands: &[Hand]) -> usize {
+    let mut hands: Vec<&Hand> = hands.iter().collect();
+    hands.sort();
+    let mut total_winnings = 0;
+    for (i, hand) in hands.iter().enumerate() {
+        let rank = i + 1;
+        total_winnings += rank * hand.bid;
+    }
+    total_winnings
+}
+
+pub fn total_poker_winnings(hands: &[PokerHand]) -> usize {
+    let mut hands: Vec<&PokerHand> = hands.iter().collect();
+    hands.sort();
+    let mut total_winnings = 0;
+    for (i, hand) in hands.iter().enumerate() {
+        let rank = i + 1;
+        total_winnings += rank * hand.bid;
+    }
+    total_winnings
+}
+
+pub fn solve_part1(game: &Game) -> usize {
+    total_winnings(&game.standard)
+}
+
+pub fn solve_part2(game: &Game) -> usize {
+    total_winnings(&game.jokers)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::hands::Hand;
-    use std::fs::File;
-    use std::io::{BufRead, BufReader};
+    use super::equity::EquityQuery;
+    use super::*;
+    use crate::hands::{
+        brute_force_substitution, parse_hands, CategoryTable, Jokers, LineError, ParseErrors,
+        ParseHandError, RuleSet, HAND_SIZE,
+    };
+
+    const SAMPLE: &str = "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483
+";
+
+    fn hand(hand_val: &str, rules: &impl RuleSet) -> Hand {
+        Hand::try_new(hand_val, 0, rules).expect("Valid hand")
+    }
+
+    fn hands(plays: &[(String, usize)], rules: &impl RuleSet) -> Vec<Hand> {
+        plays
+            .iter()
+            .map(|(hand_val, bid)| Hand::try_new(hand_val, *bid, rules).expect("Valid hand"))
+            .collect()
+    }
 
     #[test]
     fn part_1() {
-        let mut lines = BufReader::new(File::open("input").expect("Missing input")).lines();
-        let mut hands: Vec<Hand> = Vec::new();
-        while let Some(Ok(line)) = lines.next() {
-            let mut segments = line.split_whitespace();
-            let hand_val = segments.next().expect("Missing hand value");
-            let bid: usize = segments
-                .next()
-                .expect("Missing bid")
-                .parse()
-                .expect("Invalid bid");
-            let hand = Hand::from_str(&hand_val, bid);
-            hands.push(hand);
+        let game = parse(SAMPLE.as_bytes()).expect("Unable to read hands");
+        assert_eq!(solve_part1(&game), 6440);
+    }
+
+    #[test]
+    fn part_2() {
+        let game = parse(SAMPLE.as_bytes()).expect("Unable to read hands");
+        assert_eq!(solve_part2(&game), 5905);
+    }
+
+    // Jokers and deuces are both wild, and both rank below every other card
+    struct DeucesWild;
+
+    impl RuleSet for DeucesWild {
+        fn card_strength(&self, c: char) -> Option<u8> {
+            match c {
+                '2' => Some(0),
+                _ => Jokers.card_strength(c),
+            }
         }
-        hands.sort();
-        let mut total_winnings = 0;
-        for (i, hand) in hands.iter().enumerate() {
-            let rank = i + 1;
-            total_winnings += rank * hand.bid;
+
+        fn is_wild(&self, strength: u8) -> bool {
+            strength <= 1
         }
-        println!("Total winnings: {total_winnings}");
     }
 
     #[test]
-    fn part_2() {
-        let mut lines = BufReader::new(File::open("input").expect("Missing input")).lines();
-        let mut hands: Vec<Hand> = Vec::new();
-        while let Some(Ok(line)) = lines.next() {
-            let mut segments = line.split_whitespace();
-            let hand_val = segments.next().expect("Missing hand value");
-            let bid: usize = segments
-                .next()
-                .expect("Missing bid")
-                .parse()
-                .expect("Invalid bid");
-            let hand = Hand::from_str_with_jokers(&hand_val, bid);
-            hands.push(hand);
+    fn custom_rules() {
+        let hands = parse_hands(SAMPLE.as_bytes(), &DeucesWild, HAND_SIZE).expect("Valid input");
+        // 32T3K becomes three of a kind, so it swaps ranks 1 and 2 with KK677
+        assert_eq!(total_winnings(&hands), 5905 + 765 - 28);
+    }
+
+    #[test]
+    fn poker_categories() {
+        use crate::hands::PokerCategory;
+
+        let cases = [
+            ("Ah2h3h4h5h", PokerCategory::StraightFlush),
+            ("9c9d9h9sKd", PokerCategory::FourOfAKind),
+            ("3c3d3hKsKd", PokerCategory::FullHouse),
+            ("2h7h9hJhKh", PokerCategory::Flush),
+            ("Ac2d3h4s5c", PokerCategory::Straight),
+            ("TcJdQhKsAc", PokerCategory::Straight),
+            ("7c7d7h2s9c", PokerCategory::ThreeOfAKind),
+            ("7c7d2h2s9c", PokerCategory::TwoPair),
+            ("7c7d3h2s9c", PokerCategory::OnePair),
+            ("QcJd3h2s9c", PokerCategory::HighCard),
+            ("KcAd2h3s4c", PokerCategory::HighCard),
+        ];
+        for (hand_val, category) in cases {
+            assert_eq!(
+                PokerHand::new(hand_val, 0).expect("Valid hand").category(),
+                category,
+                "{hand_val}"
+            );
         }
-        hands.sort();
-        let mut total_winnings = 0;
-        for (i, hand) in hands.iter().enumerate() {
-            let rank = i + 1;
-            total_winnings += rank * hand.bid;
+    }
+
+    #[test]
+    fn poker_tie_breaks() {
+        use crate::hands::{parse_poker_hands, PokerCategory};
+
+        let hand = |hand_val| PokerHand::new(hand_val, 0).expect("Valid hand");
+        // The wheel is the lowest straight
+        assert!(hand("Ac2d3h4s5c") < hand("2c3d4h5s6c"));
+        // Pairs compare before kickers
+        assert!(hand("KcKd2h3s4c") > hand("QcQdAhKsJc"));
+        assert!(hand("KcKdAh3s4c") > hand("KhKsQh3c4d"));
+        // Two pair compares the high pair, then the low pair, then the kicker
+        assert!(hand("9c9d2h2s4c") > hand("8c8d7h7sAc"));
+        assert!(hand("KcKd3h3s4c") > hand("KhKs2h2c4d"));
+        // Suits never break ties
+        assert!(hand("Ac2d3h4s5c") == hand("Ad2c3s4h5d"));
+        let hands: Vec<PokerHand> = [("Ac2d3h4s5c", 1), ("7c7d2h2s9c", 10), ("2h7h9hJhKh", 100)]
+            .iter()
+            .map(|(hand_val, bid)| PokerHand::new(hand_val, *bid).expect("Valid hand"))
+            .collect();
+        assert_eq!(total_poker_winnings(&hands), 10 + 2 + 300);
+
+        // Malformed hands are errors, including a card dealt twice
+        let error = |hand_val| PokerHand::new(hand_val, 0).err();
+        assert_eq!(
+            error("AhAhAhAhAh"),
+            Some(ParseHandError::DuplicateCard(String::from("Ah")))
+        );
+        assert_eq!(
+            error("AhKdQc"),
+            Some(ParseHandError::WrongLength {
+                expected: 5,
+                found: 3
+            })
+        );
+        assert_eq!(
+            error("AhKdQcJsT"),
+            Some(ParseHandError::MissingSuit { index: 9 })
+        );
+        assert_eq!(
+            error("AhKdQxJsTc"),
+            Some(ParseHandError::UnknownCard {
+                card: 'x',
+                index: 5
+            })
+        );
+        assert_eq!(
+            error("AhKd1cJsTc"),
+            Some(ParseHandError::UnknownCard {
+                card: '1',
+                index: 4
+            })
+        );
+
+        // Whole lines, with positions in characters like the Camel Cards errors
+        let hand: PokerHand = "AhKdQcJsTc 3".parse().expect("Valid hand");
+        assert_eq!((hand.category(), hand.bid), (PokerCategory::Straight, 3));
+        let input = "AhKdQcJsTc 3\nAhKdQcJsT 4\n2c3c4c5c7c 5\nAhKd1cJsTc 6\n";
+        let error = parse_poker_hands(input.as_bytes()).expect_err("Bad input");
+        assert_eq!(
+            error.to_string(),
+            "line 2: missing suit at position 9\nline 4: unknown card '1' at position 4"
+        );
+        let hands =
+            parse_poker_hands("2c3c4c5c7c 5\nAhKdQcJsTc 3\n".as_bytes()).expect("Valid input");
+        assert_eq!(total_poker_winnings(&hands), 3 + 5 * 2);
+    }
+
+    // Seven card hands, where four of a kind plus three of a kind beats five of a kind
+    const SEVEN_CARD_CATEGORIES: &CategoryTable = &[
+        (&[1], "HighCard"),
+        (&[2], "OnePair"),
+        (&[2, 2], "TwoPair"),
+        (&[3], "ThreeOfAKind"),
+        (&[3, 2], "FullHouse"),
+        (&[4], "FourOfAKind"),
+        (&[5], "FiveOfAKind"),
+        (&[4, 3], "FourAndThree"),
+        (&[7], "SevenOfAKind"),
+    ];
+
+    struct SevenCard;
+
+    impl RuleSet for SevenCard {
+        fn card_strength(&self, c: char) -> Option<u8> {
+            Standard.card_strength(c)
+        }
+
+        fn categories(&self) -> &CategoryTable {
+            SEVEN_CARD_CATEGORIES
+        }
+    }
+
+    #[test]
+    fn other_hand_sizes() {
+        let hand_type = |hand_val| hand(hand_val, &Standard).hand_type().name;
+        assert_eq!(hand_type("AKQ"), "HighCard");
+        assert_eq!(hand_type("AKA"), "OnePair");
+        assert_eq!(hand_type("222"), "ThreeOfAKind");
+        assert_eq!(hand_type("2223344"), "FullHouse");
+        assert_eq!(hand_type("2222333"), "FourOfAKind");
+        assert!(hand("AKA", &Standard) < hand("222", &Standard));
+        assert!(hand("2KA", &Standard) > hand("2K9", &Standard));
+
+        let seven_card = |hand_val| hand(hand_val, &SevenCard);
+        assert_eq!(seven_card("2222333").hand_type().name, "FourAndThree");
+        assert_eq!(seven_card("AAAAAKQ").hand_type().name, "FiveOfAKind");
+        assert!(seven_card("2222333") > seven_card("AAAAAKQ"));
+        assert!(seven_card("2222222") > seven_card("AAAAKKK"));
+    }
+
+    #[test]
+    fn joker_substitutions() {
+        let substitution = Jokers.best_substitution(hand("KTJJT", &Jokers).cards());
+        assert_eq!(substitution.hand_type.name, "FourOfAKind");
+        assert_eq!(
+            substitution.describe(&Jokers),
+            "KTJJT becomes KTTTT (J at 2 as T, J at 3 as T), FourOfAKind"
+        );
+        let substitution = Jokers.best_substitution(hand("JJJJJ", &Jokers).cards());
+        assert_eq!(
+            substitution.describe(&Jokers).split(' ').nth(2),
+            Some("AAAAA")
+        );
+        let substitution = Jokers.best_substitution(hand("32T3K", &Jokers).cards());
+        assert_eq!(substitution.describe(&Jokers), "32T3K is OnePair");
+    }
+
+    // Part 2 rules over fewer cards, with every card up to `max_wild` wild
+    struct SmallDeck {
+        max_wild: u8,
+    }
+
+    impl RuleSet for SmallDeck {
+        fn card_strength(&self, c: char) -> Option<u8> {
+            Jokers.card_strength(c)
+        }
+
+        fn card_chars(&self) -> &str {
+            "J23TA"
+        }
+
+        fn is_wild(&self, strength: u8) -> bool {
+            strength <= self.max_wild
         }
-        println!("Total winnings: {total_winnings}");
+    }
+
+    #[test]
+    fn substitutions_match_brute_force() {
+        let cards: Vec<char> = "J23TA".chars().collect();
+        let mut hand_val = ['J'; 5];
+        for n in 0..cards.len().pow(5) {
+            for (i, c) in hand_val.iter_mut().enumerate() {
+                *c = cards[n / cards.len().pow(i as u32) % cards.len()];
+            }
+            let hand_val: String = hand_val.iter().collect();
+            for rules in [SmallDeck { max_wild: 0 }, SmallDeck { max_wild: 1 }] {
+                let hand = hand(&hand_val, &rules);
+                assert_eq!(
+                    hand.hand_type(),
+                    brute_force_substitution(&rules, hand.cards()).hand_type,
+                    "{hand_val}"
+                );
+            }
+        }
+
+        // With seven cards, four and three beats five of a kind, so the jokers shouldn't all
+        // go on the most common card
+        let rules = SevenCardJokers { card_chars: "J2A" };
+        let cards: Vec<char> = rules.card_chars.chars().collect();
+        let mut hand_val = ['J'; 7];
+        for n in 0..cards.len().pow(7) {
+            for (i, c) in hand_val.iter_mut().enumerate() {
+                *c = cards[n / cards.len().pow(i as u32) % cards.len()];
+            }
+            let hand_val: String = hand_val.iter().collect();
+            let hand = hand(&hand_val, &rules);
+            let substitution = rules.best_substitution(hand.cards());
+            assert_eq!(
+                substitution.hand_type,
+                brute_force_substitution(&rules, hand.cards()).hand_type,
+                "{hand_val}"
+            );
+            assert_eq!(hand.hand_type(), substitution.hand_type, "{hand_val}");
+        }
+        let rules = SevenCardJokers {
+            card_chars: "J23456789TQKA",
+        };
+        let substitution = rules.best_substitution(hand("AAAAKKJ", &rules).cards());
+        assert_eq!(
+            substitution.describe(&rules),
+            "AAAAKKJ becomes AAAAKKK (J at 6 as K), FourAndThree"
+        );
+    }
+
+    struct SevenCardJokers {
+        card_chars: &'static str,
+    }
+
+    impl RuleSet for SevenCardJokers {
+        fn card_strength(&self, c: char) -> Option<u8> {
+            Jokers.card_strength(c)
+        }
+
+        fn card_chars(&self) -> &str {
+            self.card_chars
+        }
+
+        fn is_wild(&self, strength: u8) -> bool {
+            strength == 0
+        }
+
+        fn categories(&self) -> &CategoryTable {
+            SEVEN_CARD_CATEGORIES
+        }
+    }
+
+    // Four of each card, like a real deck
+    fn full_deck() -> String {
+        "23456789TJQKA".repeat(4)
+    }
+
+    #[test]
+    fn equity_is_reproducible() {
+        use crate::hands::Jokers;
+
+        let mut query = EquityQuery {
+            partial: String::from("JJ"),
+            deck: full_deck().replacen('J', "", 2),
+            num_opponents: 3,
+            hand_size: 5,
+            trials: 5000,
+            seed: 7,
+            threads: 1,
+        };
+        let single = query.estimate(&Jokers).expect("Valid query");
+        query.threads = 4;
+        let parallel = query.estimate(&Jokers).expect("Valid query");
+        assert_eq!(single, parallel);
+        assert_eq!(single.wins + single.ties + single.losses, 5000);
+        // Two jokers guarantee three of a kind, which should usually win
+        assert!(single.win_probability() > 0.5);
+
+        query.seed = 8;
+        assert_ne!(query.estimate(&Jokers).expect("Valid query"), single);
+    }
+
+    #[test]
+    fn equity_edge_cases() {
+        let mut query = EquityQuery {
+            partial: String::from("AAAAA"),
+            deck: String::from("2345678923456789"),
+            num_opponents: 2,
+            hand_size: 5,
+            trials: 100,
+            seed: 1,
+            threads: 2,
+        };
+        let equity = query.estimate(&Standard).expect("Valid query");
+        assert_eq!(equity.win_probability(), 1.0);
+
+        // Symmetric match-up from an empty hand
+        query.partial = String::new();
+        query.deck = full_deck();
+        query.num_opponents = 1;
+        query.trials = 20000;
+        let equity = query.estimate(&Standard).expect("Valid query");
+        assert!((equity.win_probability() - equity.lose_probability()).abs() < 0.03);
+
+        query.num_opponents = 10;
+        assert!(query.estimate(&Standard).is_err());
+        query.num_opponents = usize::MAX;
+        assert!(query.estimate(&Standard).is_err());
+        query.num_opponents = 1;
+        query.deck.push('X');
+        assert!(query.estimate(&Standard).is_err());
+        query.deck.pop();
+        query.trials = 0;
+        assert!(query.estimate(&Standard).is_err());
+    }
+
+    #[test]
+    fn radix_ranking_matches_sort() {
+        use crate::ranking::{sort_key, total_winnings_radix};
+
+        let game = parse(SAMPLE.as_bytes()).expect("Unable to read hands");
+        assert_eq!(total_winnings_radix(&game.standard), 6440);
+        assert_eq!(total_winnings_radix(&game.jokers), 5905);
+
+        // Lots of hands from a small set of cards, so plenty are identical
+        let cards: Vec<char> = "J29TKA".chars().collect();
+        let mut state = 12345u64;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) as usize
+        };
+        let plays: Vec<(String, usize)> = (0..3000)
+            .map(|_| {
+                let hand_val: String = (0..5).map(|_| cards[next() % cards.len()]).collect();
+                (hand_val, next() % 1000)
+            })
+            .collect();
+        let standard = hands(&plays, &Standard);
+        assert_eq!(total_winnings_radix(&standard), total_winnings(&standard));
+        let jokers = hands(&plays, &Jokers);
+        assert_eq!(total_winnings_radix(&jokers), total_winnings(&jokers));
+        let deuces_wild = hands(&plays, &DeucesWild);
+        assert_eq!(
+            total_winnings_radix(&deuces_wild),
+            total_winnings(&deuces_wild)
+        );
+
+        for pair in plays.windows(2) {
+            let (a, b) = (&pair[0].0, &pair[1].0);
+            assert_eq!(
+                sort_key(a, &Jokers).cmp(&sort_key(b, &Jokers)),
+                hand(a, &Jokers).cmp(&hand(b, &Jokers)),
+                "{a} {b}"
+            );
+        }
+        assert_eq!(sort_key("2345678923456789", &Standard), None);
+        assert_eq!(sort_key("AKX", &Standard), None);
+
+        // Hands of different lengths, where a 2 is a real card and not padding
+        assert!(sort_key("AK", &Standard) < sort_key("AK2", &Standard));
+        let plays: Vec<(String, usize)> = (0..2000)
+            .map(|_| {
+                let len = 1 + next() % 7;
+                let hand_val: String = (0..len).map(|_| cards[next() % cards.len()]).collect();
+                (hand_val, next() % 1000)
+            })
+            .collect();
+        let standard = hands(&plays, &Standard);
+        assert_eq!(total_winnings_radix(&standard), total_winnings(&standard));
+        let jokers = hands(&plays, &Jokers);
+        assert_eq!(total_winnings_radix(&jokers), total_winnings(&jokers));
+    }
+
+    #[test]
+    fn tie_policies() {
+        use crate::ranking::{rank_hands, Tie, TiePolicy};
+
+        let game = parse(SAMPLE.as_bytes()).expect("Unable to read hands");
+        let ranking = rank_hands(&game.standard, TiePolicy::SharedAverage);
+        assert_eq!(ranking.total_winnings(), Some(6440));
+        assert!(ranking.ties.is_empty());
+
+        let game = parse("AAAAA 10\n23456 1\nAAAAA 5\n".as_bytes()).expect("Unable to read hands");
+        let ranking = rank_hands(&game.standard, TiePolicy::InputOrder);
+        assert_eq!(ranking.total_winnings(), Some(1 + 10 * 2 + 5 * 3));
+        assert_eq!(
+            ranking.ties,
+            vec![Tie {
+                indices: vec![0, 2],
+                ranks: 2..=3,
+            }]
+        );
+        let ranking = rank_hands(&game.standard, TiePolicy::BidOrder);
+        assert_eq!(ranking.total_winnings(), Some(1 + 5 * 2 + 10 * 3));
+        let ranking = rank_hands(&game.standard, TiePolicy::SharedAverage);
+        // The tied hands share rank 2.5, leaving half a point
+        assert_eq!(ranking.doubled_total_winnings, 2 + 15 * 5);
+        assert_eq!(ranking.total_winnings(), None);
+        assert_eq!(ranking.total_winnings_f64(), 1.0 + 15.0 * 2.5);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let hand: Hand = "KTJJT 220".parse().expect("Valid hand");
+        assert_eq!(hand.bid, 220);
+        assert!(Hand::try_from("KTJJT 220").is_ok());
+        assert_eq!(
+            "KTJJ 220".parse::<Hand>().err(),
+            Some(ParseHandError::WrongLength {
+                expected: 5,
+                found: 4
+            })
+        );
+        assert_eq!(
+            "KTXJT 220".parse::<Hand>().err(),
+            Some(ParseHandError::UnknownCard {
+                card: 'X',
+                index: 2
+            })
+        );
+        assert_eq!(
+            "KTJJT".parse::<Hand>().err(),
+            Some(ParseHandError::MissingBid)
+        );
+        assert_eq!(
+            "KTJJT -3".parse::<Hand>().err(),
+            Some(ParseHandError::BadBid(String::from("-3")))
+        );
+
+        let input = "32T3K 765\nT55J 684\nKK677 28\nKTJJ1 2x0\n";
+        let errors = parse_hands(input.as_bytes(), &Jokers, HAND_SIZE).expect_err("Bad input");
+        assert_eq!(
+            errors,
+            ParseErrors(vec![
+                LineError {
+                    line: 2,
+                    error: ParseHandError::WrongLength {
+                        expected: 5,
+                        found: 4
+                    }
+                },
+                LineError {
+                    line: 4,
+                    error: ParseHandError::BadBid(String::from("2x0"))
+                },
+            ])
+        );
+        let error = parse(input.as_bytes()).expect_err("Bad input");
+        assert_eq!(
+            error.to_string(),
+            "line 2: expected 5 cards but found 4\nline 4: invalid bid '2x0'"
+        );
+        let hands = parse_hands(SAMPLE.as_bytes(), &Jokers, HAND_SIZE).expect("Valid input");
+        assert_eq!(hands.len(), 5);
+
+        // Other hand sizes parse when asked for, and unknown cards are still caught
+        let input = "AKA 5\n222 7\n";
+        let game = parse_sized(input.as_bytes(), 3).expect("Valid input");
+        assert_eq!(solve_part1(&game), 5 + 7 * 2);
+        assert!(parse(input.as_bytes()).is_err());
+        let hands =
+            parse_hands("2222333 1\nAAAAAKQ 2\n".as_bytes(), &SevenCard, 7).expect("Valid input");
+        // Four and three outranks five of a kind, so the bid of 1 is ranked second
+        assert_eq!(total_winnings(&hands), 2 + 2);
+        assert_eq!(
+            parse_sized("AKX 1\n".as_bytes(), 3)
+                .err()
+                .map(|error| error.to_string()),
+            Some(String::from("line 1: unknown card 'X' at position 2"))
+        );
     }
 }
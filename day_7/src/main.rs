@@ -0,0 +1,47 @@
+use day_7::hands::{parse_poker_hands, Jokers, RuleSet, HAND_SIZE};
+use day_7::ranking::{rank_hands, TiePolicy};
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{stdin, Read};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let (flags, paths): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let input: Box<dyn Read> = match paths.first() {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(stdin().lock()),
+    };
+    let hand_size = match flags.iter().find_map(|flag| flag.strip_prefix("--cards=")) {
+        Some(hand_size) => hand_size.parse()?,
+        None => HAND_SIZE,
+    };
+    if flags.iter().any(|flag| flag == "--poker") {
+        let hands = parse_poker_hands(input)?;
+        println!("Poker: {}", day_7::total_poker_winnings(&hands));
+        return Ok(());
+    }
+    let game = day_7::parse_sized(input, hand_size)?;
+    println!("Part 1: {}", day_7::solve_part1(&game));
+    println!("Part 2: {}", day_7::solve_part2(&game));
+
+    if flags.iter().any(|flag| flag == "--explain") {
+        for hand in &game.jokers {
+            println!(
+                "{}",
+                Jokers.best_substitution(hand.cards()).describe(&Jokers)
+            );
+        }
+    }
+    if flags.iter().any(|flag| flag == "--ties") {
+        for (part, ranking) in [
+            (1, rank_hands(&game.standard, TiePolicy::InputOrder)),
+            (2, rank_hands(&game.jokers, TiePolicy::InputOrder)),
+        ] {
+            for tie in ranking.ties {
+                println!("Part {part} tie: {tie:?}");
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,166 +1,1304 @@
-mod graph {
+pub mod graph {
     use std::collections::HashMap;
+    use std::error::Error;
+    use std::fmt;
 
-    pub type NodeId = [char; 3];
+    /// Dense index of an interned node label.
+    pub type NodeId = u32;
 
-    #[derive(Debug)]
-    pub struct Node {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Node<'a> {
         pub id: NodeId,
-        pub left: NodeId,
-        pub right: NodeId,
+        /// Indexed by `Direction::index`, so left then right for the puzzle's nodes.
+        pub successors: &'a [NodeId],
     }
 
+    impl Node<'_> {
+        pub fn left(&self) -> Option<NodeId> {
+            self.successors.first().copied()
+        }
+
+        pub fn right(&self) -> Option<NodeId> {
+            self.successors.get(1).copied()
+        }
+    }
+
+    /// Nodes stored as one flat successor array, with each node's slice of it indexed by
+    /// `NodeId`. A label referenced as a successor gets an id straight away, but only counts
+    /// as a node once it's been added.
+    #[derive(Default)]
     pub struct Graph {
-        node_map: HashMap<NodeId, Node>,
+        labels: Vec<String>,
+        ids: HashMap<String, NodeId>,
+        successors: Vec<NodeId>,
+        first_successor: Vec<usize>,
+        num_successors: Vec<usize>,
+        defined: Vec<bool>,
     }
 
     impl Graph {
         pub fn new() -> Self {
-            Self {
-                node_map: HashMap::new(),
+            Self::default()
+        }
+
+        /// Id for a label, creating one if it hasn't been seen before.
+        pub fn intern(&mut self, label: &str) -> NodeId {
+            if let Some(id) = self.ids.get(label) {
+                return *id;
             }
+            let id = self.labels.len() as NodeId;
+            self.labels.push(label.to_string());
+            self.ids.insert(label.to_string(), id);
+            self.first_successor.push(0);
+            self.num_successors.push(0);
+            self.defined.push(false);
+            id
+        }
+
+        pub fn add_node(&mut self, label: &str, successors: &[&str]) -> NodeId {
+            let id = self.intern(label);
+            let successor_ids: Vec<NodeId> = successors
+                .iter()
+                .map(|successor| self.intern(successor))
+                .collect();
+            self.first_successor[id as usize] = self.successors.len();
+            self.num_successors[id as usize] = successor_ids.len();
+            self.successors.extend(successor_ids);
+            self.defined[id as usize] = true;
+            id
+        }
+
+        pub fn id(&self, label: &str) -> Option<NodeId> {
+            self.ids.get(label).copied()
+        }
+
+        pub fn label(&self, id: NodeId) -> &str {
+            &self.labels[id as usize]
         }
 
-        pub fn add_node(&mut self, node: Node) {
-            self.node_map.insert(node.id, node);
+        /// Number of interned labels, including any that were never added as nodes.
+        pub fn len(&self) -> usize {
+            self.labels.len()
         }
 
-        pub fn get_node(&self, id: &NodeId) -> Option<&Node> {
-            self.node_map.get(id)
+        pub fn is_empty(&self) -> bool {
+            self.labels.is_empty()
+        }
+
+        pub fn get_node(&self, id: NodeId) -> Option<Node<'_>> {
+            match self.defined.get(id as usize) {
+                Some(true) => {
+                    let first = self.first_successor[id as usize];
+                    Some(Node {
+                        id,
+                        successors: &self.successors
+                            [first..first + self.num_successors[id as usize]],
+                    })
+                }
+                _ => None,
+            }
         }
 
-        pub fn nodes(&self) -> impl Iterator<Item = &NodeId> {
-            self.node_map.keys()
+        pub fn nodes(&self) -> impl Iterator<Item = NodeId> + '_ {
+            (0..self.labels.len() as NodeId).filter(|id| self.defined[*id as usize])
         }
 
+        /// Steps from `from` until `exit_condition` holds, or an error if the walk starts a
+        /// pass through the directions on a node it already started one on, since it would
+        /// then loop forever.
         pub fn num_steps<F>(
             &self,
             from: NodeId,
             exit_condition: F,
-            directions: &Vec<Direction>,
-        ) -> usize
+            directions: &[Direction],
+        ) -> Result<usize, WalkError>
         where
             F: Fn(NodeId) -> bool,
         {
+            if directions.is_empty() && !exit_condition(from) {
+                return Err(WalkError::NoDirections);
+            }
+            // Only checked at the start of each pass, which keeps the inner loop tight
+            let mut pass_starts: Vec<Option<usize>> = vec![None; self.labels.len()];
             let mut current_node_id = from;
             let mut num_moves = 0;
-            let mut direction_iter = directions.iter().cycle();
             while !exit_condition(current_node_id) {
-                let current_node = self
-                    .get_node(&current_node_id)
-                    .expect("Invalid current node");
-                let direction = direction_iter
-                    .next()
-                    .expect("Ran out of directions somehow");
-                match direction {
-                    Direction::Left => {
-                        current_node_id =
-                            self.get_node(&current_node.left).expect("No left node").id
-                    }
-                    Direction::Right => {
-                        current_node_id = self
-                            .get_node(&current_node.right)
-                            .expect("No right node")
-                            .id
+                let direction_index = num_moves % directions.len();
+                if direction_index == 0 {
+                    let pass_start = &mut pass_starts[current_node_id as usize];
+                    if let Some(cycle_entry) = *pass_start {
+                        return Err(WalkError::Unreachable {
+                            cycle_entry,
+                            cycle_len: num_moves - cycle_entry,
+                        });
                     }
+                    *pass_start = Some(num_moves);
                 }
+                current_node_id = self.step(current_node_id, &directions[direction_index])?;
                 num_moves += 1;
             }
-            num_moves
+            Ok(num_moves)
+        }
+
+        /// Every node visited on the way from `from` to the exit, including both ends.
+        pub fn path<F>(
+            &self,
+            from: NodeId,
+            exit_condition: F,
+            directions: &[Direction],
+        ) -> Result<Vec<NodeId>, WalkError>
+        where
+            F: Fn(NodeId) -> bool,
+        {
+            // Finding the length first makes sure the walk ends
+            let num_steps = self.num_steps(from, exit_condition, directions)?;
+            let mut path = Vec::with_capacity(num_steps + 1);
+            path.push(from);
+            let mut current_node_id = from;
+            for direction in directions.iter().cycle().take(num_steps) {
+                current_node_id = self.step(current_node_id, direction)?;
+                path.push(current_node_id);
+            }
+            Ok(path)
+        }
+
+        pub fn step(&self, from: NodeId, direction: &Direction) -> Result<NodeId, WalkError> {
+            let current_node = self
+                .get_node(from)
+                .ok_or_else(|| WalkError::MissingNode(self.label_or_id(from)))?;
+            let branch = direction.index();
+            let next =
+                *current_node
+                    .successors
+                    .get(branch)
+                    .ok_or_else(|| WalkError::MissingBranch {
+                        from: self.label(from).to_string(),
+                        branch,
+                    })?;
+            if self.defined[next as usize] {
+                return Ok(next);
+            }
+            let (from, to) = (self.label(from).to_string(), self.label(next).to_string());
+            Err(match direction {
+                Direction::Left => WalkError::DanglingLeft { from, to },
+                Direction::Right => WalkError::DanglingRight { from, to },
+                Direction::Branch(branch) => WalkError::DanglingBranch {
+                    from,
+                    branch: *branch,
+                    to,
+                },
+            })
+        }
+
+        fn label_or_id(&self, id: NodeId) -> String {
+            match self.labels.get(id as usize) {
+                Some(label) => label.clone(),
+                None => format!("#{id}"),
+            }
         }
     }
 
+    #[derive(Debug, PartialEq)]
+    pub enum WalkError {
+        /// The walk entered a cycle by step `cycle_entry` without meeting the exit condition.
+        Unreachable {
+            cycle_entry: usize,
+            cycle_len: usize,
+        },
+        MissingNode(String),
+        DanglingLeft {
+            from: String,
+            to: String,
+        },
+        DanglingRight {
+            from: String,
+            to: String,
+        },
+        DanglingBranch {
+            from: String,
+            branch: usize,
+            to: String,
+        },
+        /// The node has fewer successors than the direction needs.
+        MissingBranch {
+            from: String,
+            branch: usize,
+        },
+        NoDirections,
+        /// The step count, or the period it repeats with, doesn't fit in a u128.
+        StepOverflow,
+    }
+
+    impl fmt::Display for WalkError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                WalkError::Unreachable {
+                    cycle_entry,
+                    cycle_len,
+                } => write!(
+                    f,
+                    "exit never reached, walk loops every {cycle_len} steps from step {cycle_entry}"
+                ),
+                WalkError::MissingNode(label) => write!(f, "no node {label}"),
+                WalkError::DanglingLeft { from, to } => {
+                    write!(f, "{from} goes left to missing node {to}")
+                }
+                WalkError::DanglingRight { from, to } => {
+                    write!(f, "{from} goes right to missing node {to}")
+                }
+                WalkError::DanglingBranch { from, branch, to } => {
+                    write!(f, "{from} goes down branch {branch} to missing node {to}")
+                }
+                WalkError::MissingBranch { from, branch } => {
+                    write!(f, "{from} has no branch {branch}")
+                }
+                WalkError::NoDirections => write!(f, "no directions to follow"),
+                WalkError::StepOverflow => write!(f, "step count overflowed 128 bits"),
+            }
+        }
+    }
+
+    impl Error for WalkError {}
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
     pub enum Direction {
         Left,
         Right,
+        /// Any successor by position, for nodes with more than two.
+        Branch(usize),
     }
 
-    fn gcd(mut a: usize, mut b: usize) -> usize {
-        if a == b {
-            return a;
+    impl Direction {
+        /// Position of the successor this direction follows.
+        pub fn index(&self) -> usize {
+            match self {
+                Direction::Left => 0,
+                Direction::Right => 1,
+                Direction::Branch(branch) => *branch,
+            }
         }
-        if b > a {
-            let temp = a;
-            a = b;
-            b = temp;
+
+        /// L and R, or a digit or lowercase letter naming the branch (0-9, then a = 0 to z = 25).
+        pub fn from_char(c: char) -> Option<Self> {
+            match c {
+                'L' => Some(Direction::Left),
+                'R' => Some(Direction::Right),
+                '0'..='9' => Some(Direction::Branch(c as usize - '0' as usize)),
+                'a'..='z' => Some(Direction::Branch(c as usize - 'a' as usize)),
+                _ => None,
+            }
         }
+    }
+}
+
+pub mod arith {
+    use num_bigint::BigUint;
+    use std::error::Error;
+    use std::fmt;
+
+    pub fn gcd(mut a: u128, mut b: u128) -> u128 {
         while b > 0 {
-            let temp = a;
-            a = b;
-            b = temp % b;
+            (a, b) = (b, a % b);
+        }
+        a
+    }
+
+    /// LCM of two usizes, or None if it doesn't fit.
+    pub fn checked_lcm(a: usize, b: usize) -> Option<usize> {
+        if a == 0 || b == 0 {
+            return Some(0);
+        }
+        (a / gcd(a as u128, b as u128) as usize).checked_mul(b)
+    }
+
+    /// An LCM in the narrowest type it fits.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Lcm {
+        Small(usize),
+        Wide(u128),
+        Big(BigUint),
+    }
+
+    impl Lcm {
+        pub fn lcm(self, n: usize) -> Lcm {
+            match self {
+                Lcm::Small(a) => match checked_lcm(a, n) {
+                    Some(lcm) => Lcm::Small(lcm),
+                    // Both fit in 64 bits, so the product fits in 128
+                    None => Lcm::Wide(a as u128 / gcd(a as u128, n as u128) * n as u128),
+                },
+                Lcm::Wide(a) => {
+                    let factor = n as u128 / gcd(a, n as u128);
+                    match a.checked_mul(factor) {
+                        Some(lcm) => Lcm::Wide(lcm),
+                        None => Lcm::Big(BigUint::from(a) * factor),
+                    }
+                }
+                Lcm::Big(a) => {
+                    // gcd(a, n) = gcd(n, a mod n), which keeps the big side to one division
+                    let remainder = u128::try_from(&a % n).expect("Remainder is below n");
+                    Lcm::Big(a * (n as u128 / gcd(n as u128, remainder)))
+                }
+            }
+        }
+
+        pub fn to_u128(&self) -> Option<u128> {
+            match self {
+                Lcm::Small(lcm) => Some(*lcm as u128),
+                Lcm::Wide(lcm) => Some(*lcm),
+                Lcm::Big(lcm) => u128::try_from(lcm).ok(),
+            }
+        }
+    }
+
+    impl fmt::Display for Lcm {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Lcm::Small(lcm) => write!(f, "{lcm}"),
+                Lcm::Wide(lcm) => write!(f, "{lcm}"),
+                Lcm::Big(lcm) => write!(f, "{lcm}"),
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub enum LcmError {
+        Empty,
+        /// Zero has no useful LCM, and as a cycle length it means a walker never moved.
+        Zero {
+            index: usize,
+        },
+    }
+
+    impl fmt::Display for LcmError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                LcmError::Empty => write!(f, "no numbers to take the LCM of"),
+                LcmError::Zero { index } => write!(f, "number {index} is zero"),
+            }
         }
-        return a;
     }
 
-    pub fn lcm(a: usize, b: usize) -> usize {
-        return a * (b / gcd(a, b));
+    impl Error for LcmError {}
+
+    /// LCM of every number, widening to u128 and then a `BigUint` instead of overflowing.
+    pub fn lcm_all(numbers: impl IntoIterator<Item = usize>) -> Result<Lcm, LcmError> {
+        let mut lcm: Option<Lcm> = None;
+        for (index, n) in numbers.into_iter().enumerate() {
+            if n == 0 {
+                return Err(LcmError::Zero { index });
+            }
+            lcm = Some(lcm.unwrap_or(Lcm::Small(1)).lcm(n));
+        }
+        lcm.ok_or(LcmError::Empty)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::graph::{lcm, Direction, Graph, Node, NodeId};
-    use std::fs::File;
-    use std::io::{BufRead, BufReader, Read};
-
-    pub fn parse_graph(input: impl Read) -> (Vec<Direction>, Graph) {
-        let mut lines = BufReader::new(input).lines();
-        let direction_line = lines
-            .next()
-            .expect("Unexpected EOF")
-            .expect("No directions line");
-        let directions: Vec<Direction> = direction_line
-            .chars()
-            .map(|c| match c {
-                'L' => Direction::Left,
-                'R' => Direction::Right,
-                _ => panic!("Unknown direction"),
+pub mod cycles {
+    use crate::arith::{gcd, lcm_all, Lcm};
+    use crate::graph::{Direction, Graph, NodeId, WalkError};
+    use std::collections::HashMap;
+
+    /// The steps at which one walker is on an exit node, as a one-off prefix followed by a
+    /// cycle that repeats forever.
+    #[derive(Debug, PartialEq)]
+    pub struct Cycle {
+        /// Step at which the walker first reaches a (node, instruction index) state that repeats.
+        pub entry: usize,
+        pub length: usize,
+        /// Exit hits before `entry`, which never happen again.
+        pub prefix_hits: Vec<usize>,
+        /// Exit hits in `entry..entry + length`, which repeat every `length` steps.
+        pub cycle_hits: Vec<usize>,
+    }
+
+    impl Cycle {
+        pub fn hits(&self, step: u128) -> bool {
+            if step < self.entry as u128 {
+                return self.prefix_hits.iter().any(|hit| *hit as u128 == step);
+            }
+            self.cycle_hits.iter().any(|hit| {
+                let hit = *hit as u128;
+                step >= hit && (step - hit).is_multiple_of(self.length as u128)
             })
-            .collect();
-        lines.next();
+        }
+
+        pub fn first_hit(&self) -> Option<usize> {
+            self.prefix_hits
+                .first()
+                .or(self.cycle_hits.first())
+                .copied()
+        }
+    }
+
+    pub fn find_cycle<F>(
+        graph: &Graph,
+        from: NodeId,
+        exit_condition: F,
+        directions: &[Direction],
+    ) -> Result<Cycle, WalkError>
+    where
+        F: Fn(NodeId) -> bool,
+    {
+        if directions.is_empty() {
+            return Err(WalkError::NoDirections);
+        }
+        let mut first_seen: HashMap<(NodeId, usize), usize> = HashMap::new();
+        let mut hits = Vec::new();
+        let mut current_node_id = from;
+        let mut step = 0;
+        loop {
+            let direction_index = step % directions.len();
+            if let Some(entry) = first_seen.insert((current_node_id, direction_index), step) {
+                let (prefix_hits, cycle_hits) = hits.iter().partition(|hit| **hit < entry);
+                return Ok(Cycle {
+                    entry,
+                    length: step - entry,
+                    prefix_hits,
+                    cycle_hits,
+                });
+            }
+            if exit_condition(current_node_id) {
+                hits.push(step);
+            }
+            current_node_id = graph.step(current_node_id, &directions[direction_index])?;
+            step += 1;
+        }
+    }
+
+    // Inverse of a modulo m, for coprime a and m
+    fn mod_inverse(a: u128, m: u128) -> u128 {
+        let (mut old_r, mut r) = (a as i128, m as i128);
+        let (mut old_s, mut s) = (1i128, 0i128);
+        while r != 0 {
+            let quotient = old_r / r;
+            (old_r, r) = (r, old_r - quotient * r);
+            (old_s, s) = (s, old_s - quotient * s);
+        }
+        old_s.rem_euclid(m as i128) as u128
+    }
+
+    /// Solves x = a1 (mod m1) and x = a2 (mod m2) for moduli that needn't be coprime,
+    /// returning x and the combined modulus, or None if there's no solution. The second
+    /// modulus is a cycle length, so it has to fit in 64 bits for the products below.
+    pub fn crt(a1: u128, m1: u128, a2: u128, m2: u128) -> Result<Option<(u128, u128)>, WalkError> {
+        let g = gcd(m1, m2);
+        if a1 % g != a2 % g {
+            return Ok(None);
+        }
+        let reduced_m2 = m2 / g;
+        // x = a1 + m1 * t, where t = (a2 - a1) / g * (m1 / g)^-1 (mod m2 / g)
+        let diff = (a2 % m2 + m2 - a1 % m2) % m2 / g;
+        let inverse = mod_inverse(m1 / g % reduced_m2, reduced_m2);
+        let t = diff % reduced_m2 * inverse % reduced_m2;
+        let modulus = m1.checked_mul(reduced_m2).ok_or(WalkError::StepOverflow)?;
+        // t < m2 / g, so m1 * t + a1 % m1 < modulus
+        let x = a1 % m1 + m1 * t;
+        Ok(Some((x, modulus)))
+    }
+
+    /// First step at which every walker is on an exit node at once, or an error if the
+    /// walkers' combined period doesn't fit in a u128.
+    pub fn first_common_hit(cycles: &[Cycle]) -> Result<Option<u128>, WalkError> {
+        let Some(latest_entry) = cycles.iter().max_by_key(|cycle| cycle.entry) else {
+            return Ok(None);
+        };
+
+        // Before every walker is in its cycle, only the latest one's prefix hits can work
+        for hit in &latest_entry.prefix_hits {
+            if cycles.iter().all(|cycle| cycle.hits(*hit as u128)) {
+                return Ok(Some(*hit as u128));
+            }
+        }
+
+        // Otherwise try every combination of one cycle hit per walker
+        let min_step = latest_entry.entry as u128;
+        let mut best: Option<u128> = None;
+        let mut choice = vec![0usize; cycles.len()];
+        if cycles.iter().any(|cycle| cycle.cycle_hits.is_empty()) {
+            return Ok(None);
+        }
+        loop {
+            let mut solution = Some((0u128, 1u128));
+            for (cycle, i) in cycles.iter().zip(&choice) {
+                let Some((a, m)) = solution else { break };
+                solution = crt(a, m, cycle.cycle_hits[*i] as u128, cycle.length as u128)?;
+            }
+            if let Some((mut x, modulus)) = solution {
+                if x < min_step {
+                    x = (min_step - x)
+                        .div_ceil(modulus)
+                        .checked_mul(modulus)
+                        .and_then(|offset| offset.checked_add(x))
+                        .ok_or(WalkError::StepOverflow)?;
+                }
+                best = Some(best.map_or(x, |best| best.min(x)));
+            }
+
+            // Advance to the next combination, like an odometer
+            let mut i = 0;
+            while i < choice.len() && choice[i] + 1 >= cycles[i].cycle_hits.len() {
+                choice[i] = 0;
+                i += 1;
+            }
+            if i == choice.len() {
+                return Ok(best);
+            }
+            choice[i] += 1;
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct GhostAnalysis {
+        pub cycles: Vec<Cycle>,
+        /// Steps until every walker is on an exit node at once, if that ever happens.
+        pub steps: Option<u128>,
+        /// LCM of each walker's first exit hit, which the puzzle inputs are built to make
+        /// correct. None if a walker never hits an exit or hits one at step 0.
+        pub lcm_shortcut: Option<Lcm>,
+        pub lcm_shortcut_valid: bool,
+    }
+
+    pub fn analyse<F>(
+        graph: &Graph,
+        starts: &[NodeId],
+        exit_condition: F,
+        directions: &[Direction],
+    ) -> Result<GhostAnalysis, WalkError>
+    where
+        F: Fn(NodeId) -> bool,
+    {
+        let cycles: Vec<Cycle> = starts
+            .iter()
+            .map(|start| find_cycle(graph, *start, &exit_condition, directions))
+            .collect::<Result<_, _>>()?;
+        let steps = first_common_hit(&cycles)?;
+        let lcm_shortcut = cycles
+            .iter()
+            .map(Cycle::first_hit)
+            .collect::<Option<Vec<usize>>>()
+            .and_then(|hits| lcm_all(hits).ok());
+        Ok(GhostAnalysis {
+            lcm_shortcut_valid: steps.is_some()
+                && steps == lcm_shortcut.as_ref().and_then(Lcm::to_u128),
+            cycles,
+            steps,
+            lcm_shortcut,
+        })
+    }
+}
+
+pub mod simulate {
+    use crate::graph::{Direction, Graph, NodeId, WalkError};
+
+    /// Which walkers were on exit nodes after a given number of steps.
+    #[derive(Debug, PartialEq)]
+    pub struct Snapshot {
+        pub step: usize,
+        /// Indices into the starting walkers.
+        pub on_exit: Vec<usize>,
+    }
+
+    /// Passed to the progress callback every `progress_interval` steps.
+    #[derive(Debug, PartialEq)]
+    pub struct Progress {
+        pub step: usize,
+        pub on_exit: usize,
+        /// Most walkers seen on exit nodes at once so far.
+        pub best: usize,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub struct Simulation {
+        /// Step at which every walker was on an exit node, if it happened within budget.
+        pub steps: Option<usize>,
+        pub steps_taken: usize,
+        pub positions: Vec<NodeId>,
+        /// Only steps where at least one walker was on an exit, to keep long runs small.
+        pub snapshots: Vec<Snapshot>,
+    }
+
+    /// Walk every start in lockstep, one instruction at a time, as the puzzle describes.
+    pub struct Lockstep<'a> {
+        pub starts: &'a [NodeId],
+        /// Most steps to take before giving up.
+        pub budget: usize,
+        /// Steps between progress callbacks, or 0 for none.
+        pub progress_interval: usize,
+        pub record_snapshots: bool,
+    }
 
-        let mut graph = Graph::new();
-        while let Some(Ok(line)) = lines.next() {
-            let chars: Vec<char> = line.chars().collect();
-            let parent_id = &chars[0..3];
-            let left_id = &chars[7..10];
-            let right_id = &chars[12..15];
-            let node = Node {
-                id: [parent_id[0], parent_id[1], parent_id[2]],
-                left: [left_id[0], left_id[1], left_id[2]],
-                right: [right_id[0], right_id[1], right_id[2]],
-            };
-            graph.add_node(node);
+    impl Lockstep<'_> {
+        pub fn run<F, P>(
+            &self,
+            graph: &Graph,
+            exit_condition: F,
+            directions: &[Direction],
+            mut on_progress: P,
+        ) -> Result<Simulation, WalkError>
+        where
+            F: Fn(NodeId) -> bool,
+            P: FnMut(&Progress),
+        {
+            if directions.is_empty() {
+                return Err(WalkError::NoDirections);
+            }
+            let mut positions = self.starts.to_vec();
+            let mut snapshots = Vec::new();
+            let mut best = 0;
+            let mut step = 0;
+            loop {
+                // Counted without allocating, since this runs on every step
+                let on_exit = positions.iter().filter(|id| exit_condition(**id)).count();
+                best = best.max(on_exit);
+                if self.progress_interval > 0 && step % self.progress_interval == 0 {
+                    on_progress(&Progress {
+                        step,
+                        on_exit,
+                        best,
+                    });
+                }
+                let all_on_exit = on_exit == positions.len();
+                if self.record_snapshots && on_exit > 0 {
+                    let on_exit = positions
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, id)| exit_condition(**id))
+                        .map(|(index, _)| index)
+                        .collect();
+                    snapshots.push(Snapshot { step, on_exit });
+                }
+                if all_on_exit || step == self.budget {
+                    return Ok(Simulation {
+                        steps: all_on_exit.then_some(step),
+                        steps_taken: step,
+                        positions,
+                        snapshots,
+                    });
+                }
+
+                let direction = &directions[step % directions.len()];
+                for id in positions.iter_mut() {
+                    *id = graph.step(*id, direction)?;
+                }
+                step += 1;
+            }
         }
-        (directions, graph)
     }
+}
+
+pub mod export {
+    use crate::graph::{Graph, NodeId};
+    use std::collections::HashSet;
+
+    fn escape(label: &str) -> String {
+        label.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+
+    // JSON also forbids raw control characters inside strings
+    fn escape_json(label: &str) -> String {
+        let mut escaped = String::with_capacity(label.len());
+        for c in label.chars() {
+            match c {
+                '\\' | '"' => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
+
+    /// GraphViz source for the network, with start (..A) nodes in green, end (..Z) nodes in
+    /// red and, if given, the edges along `path` drawn in bold.
+    pub fn to_dot(graph: &Graph, path: Option<&[NodeId]>) -> String {
+        let path_edges: HashSet<(NodeId, NodeId)> = path
+            .map(|path| path.windows(2).map(|pair| (pair[0], pair[1])).collect())
+            .unwrap_or_default();
+        let mut dot = String::from("digraph network {\n");
+        for id in graph.nodes() {
+            let label = graph.label(id);
+            if label.ends_with('A') {
+                dot.push_str(&format!(
+                    "    \"{}\" [style=filled, fillcolor=palegreen];\n",
+                    escape(label)
+                ));
+            } else if label.ends_with('Z') {
+                dot.push_str(&format!(
+                    "    \"{}\" [style=filled, fillcolor=salmon];\n",
+                    escape(label)
+                ));
+            }
+        }
+        for id in graph.nodes() {
+            let node = graph.get_node(id).expect("Listed nodes exist");
+            // One edge per successor, labelled with every branch that leads there
+            let mut edges: Vec<(NodeId, String)> = Vec::new();
+            for (branch, to) in node.successors.iter().enumerate() {
+                let name = match (node.successors.len(), branch) {
+                    (2, 0) => String::from("L"),
+                    (2, 1) => String::from("R"),
+                    _ => branch.to_string(),
+                };
+                match edges.iter_mut().find(|(existing, _)| existing == to) {
+                    Some((_, label)) if node.successors.len() == 2 => label.push_str(&name),
+                    Some((_, label)) => label.push_str(&format!(",{name}")),
+                    None => edges.push((*to, name)),
+                }
+            }
+            for (to, label) in edges {
+                let style = if path_edges.contains(&(id, to)) {
+                    ", color=blue, penwidth=3"
+                } else {
+                    ""
+                };
+                dot.push_str(&format!(
+                    "    \"{}\" -> \"{}\" [label=\"{}\"{}];\n",
+                    escape(graph.label(id)),
+                    escape(graph.label(to)),
+                    label,
+                    style
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The network as a JSON adjacency list.
+    pub fn to_json(graph: &Graph) -> String {
+        let nodes: Vec<String> = graph
+            .nodes()
+            .map(|id| {
+                let node = graph.get_node(id).expect("Listed nodes exist");
+                let label = graph.label(id);
+                let successors: Vec<String> = node
+                    .successors
+                    .iter()
+                    .map(|successor| format!("\"{}\"", escape_json(graph.label(*successor))))
+                    .collect();
+                format!(
+                    "{{\"id\": \"{}\", \"successors\": [{}], \"start\": {}, \"end\": {}}}",
+                    escape_json(label),
+                    successors.join(", "),
+                    label.ends_with('A'),
+                    label.ends_with('Z')
+                )
+            })
+            .collect();
+        format!("{{\"nodes\": [{}]}}", nodes.join(", "))
+    }
+}
+
+use graph::{Direction, Graph, NodeId, WalkError};
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read};
+
+pub fn parse(input: impl Read) -> Result<(Vec<Direction>, Graph), Box<dyn Error>> {
+    let mut lines = BufReader::new(input).lines();
+    let direction_line = lines.next().ok_or("Unexpected EOF")??;
+    let directions: Vec<Direction> = direction_line
+        .chars()
+        .map(|c| Direction::from_char(c).ok_or("Unknown direction"))
+        .collect::<Result<_, _>>()?;
+    lines.next();
+
+    let mut graph = Graph::new();
+    for line in lines {
+        let line = line?;
+        // AAA = (BBB, CCC), with any number of successors
+        let (label, successors) = line.split_once(" = ").ok_or("Invalid node line")?;
+        let successors: Vec<&str> = successors
+            .strip_prefix('(')
+            .and_then(|successors| successors.strip_suffix(')'))
+            .ok_or("Invalid node line")?
+            .split(',')
+            .map(|successor| successor.trim())
+            .collect();
+        graph.add_node(label.trim(), &successors);
+    }
+    Ok((directions, graph))
+}
+
+pub fn solve_part1(directions: &[Direction], graph: &Graph) -> Result<usize, WalkError> {
+    let start = graph
+        .id("AAA")
+        .ok_or_else(|| WalkError::MissingNode(String::from("AAA")))?;
+    let exit = graph.id("ZZZ");
+    graph.num_steps(start, |node_id| Some(node_id) == exit, directions)
+}
+
+pub fn solve_part2(directions: &[Direction], graph: &Graph) -> Result<u128, Box<dyn Error>> {
+    let start_node_ids: Vec<NodeId> = graph
+        .nodes()
+        .filter(|id| graph.label(*id).ends_with('A'))
+        .collect();
+    let is_exit: Vec<bool> = (0..graph.len())
+        .map(|id| graph.label(id as NodeId).ends_with('Z'))
+        .collect();
+    let analysis = cycles::analyse(
+        graph,
+        &start_node_ids,
+        |id| is_exit[id as usize],
+        directions,
+    )?;
+    Ok(analysis.steps.ok_or("Ghosts never all reach Z at once")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cycles::Cycle;
+    use super::*;
+
+    const SAMPLE_1: &str = "LLR
+
+AAA = (BBB, BBB)
+BBB = (AAA, ZZZ)
+ZZZ = (ZZZ, ZZZ)
+";
+
+    const SAMPLE_2: &str = "LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)
+";
 
     #[test]
     fn part_1() {
-        let file = File::open("input").expect("Expected a file called 'input'");
-        let (directions, graph) = parse_graph(file);
-        let num_moves = graph.num_steps(
-            ['A', 'A', 'A'],
-            |node_id| node_id == ['Z', 'Z', 'Z'],
-            &directions,
-        );
-        println!("Number of moves: {num_moves}");
+        let (directions, graph) = parse(SAMPLE_1.as_bytes()).expect("Unable to read graph");
+        assert_eq!(solve_part1(&directions, &graph), Ok(6));
     }
 
     #[test]
     fn part_2() {
-        let file = File::open("input").expect("Expected a file called 'input'");
-        let (directions, graph) = parse_graph(file);
-        let mut start_node_ids: Vec<&NodeId> = graph.nodes().filter(|id| id[2] == 'A').collect();
-        let min_num_steps: Vec<usize> = start_node_ids
+        let (directions, graph) = parse(SAMPLE_2.as_bytes()).expect("Unable to read graph");
+        assert_eq!(solve_part2(&directions, &graph).expect("Reachable"), 6);
+    }
+
+    // Walk every ghost together until they're all on Z, as the puzzle describes
+    fn ids(graph: &Graph, labels: &[&str]) -> Vec<NodeId> {
+        labels
             .iter()
-            .map(|node_id| graph.num_steps(**node_id, |id| id[2] == 'Z', &directions))
+            .map(|label| graph.id(label).expect("Known label"))
+            .collect()
+    }
+
+    fn brute_force_ghosts(directions: &[Direction], graph: &Graph, limit: usize) -> Option<u128> {
+        let mut current: Vec<NodeId> = graph
+            .nodes()
+            .filter(|id| graph.label(*id).ends_with('A'))
             .collect();
-        // Get LCM of the shortest paths for each
-        let num_steps: usize = min_num_steps
+        for step in 0..limit {
+            if current.iter().all(|id| graph.label(*id).ends_with('Z')) {
+                return Some(step as u128);
+            }
+            let direction = &directions[step % directions.len()];
+            for id in current.iter_mut() {
+                *id = graph.step(*id, direction).expect("Valid graph");
+            }
+        }
+        None
+    }
+
+    // 11A has a tail of one step into a two-step cycle, and 22A reaches 22Z after one step
+    // and then every three, so the first Z distances are 2 and 1 but the answer is 4
+    const OFFSET_CYCLES: &str = "L
+
+11A = (11B, 11B)
+11B = (11Z, 11Z)
+11Z = (11B, 11B)
+22A = (22Z, 22Z)
+22Z = (22C, 22C)
+22C = (22D, 22D)
+22D = (22Z, 22Z)
+";
+
+    // 11A passes 11Z once at step 3 and then loops from step 5 without it, while 22A only
+    // reaches 22Z at step 8 of a ten-step loop, so they never line up
+    const LATE_CYCLE_HIT: &str = "L
+
+11A = (11B, 11B)
+11B = (11C, 11C)
+11C = (11Z, 11Z)
+11Z = (11D, 11D)
+11D = (11E, 11E)
+11E = (11F, 11F)
+11F = (11E, 11E)
+22A = (22B, 22B)
+22B = (22C, 22C)
+22C = (22D, 22D)
+22D = (22E, 22E)
+22E = (22F, 22F)
+22F = (22G, 22G)
+22G = (22H, 22H)
+22H = (22Z, 22Z)
+22Z = (22I, 22I)
+22I = (22A, 22A)
+";
+
+    // 33A sees two Zs per lap of a five-step loop, and 44A sees one per lap of a three-step
+    // loop, so each walker's state cycle has several hits once the directions are folded in
+    const MULTIPLE_HITS: &str = "LR
+
+33A = (33Z, 33Z)
+33Z = (34Z, 34Z)
+34Z = (33B, 33B)
+33B = (33C, 33C)
+33C = (33A, 33A)
+44A = (44B, 44B)
+44B = (44C, 44C)
+44C = (44Z, 44Z)
+44Z = (44B, 44B)
+";
+
+    #[test]
+    fn ghost_cycles() {
+        let (directions, graph) = parse(SAMPLE_2.as_bytes()).expect("Unable to read graph");
+        let analysis = cycles::analyse(
+            &graph,
+            &ids(&graph, &["11A", "22A"]),
+            |id| graph.label(id).ends_with('Z'),
+            &directions,
+        )
+        .expect("Valid graph");
+        assert_eq!(analysis.steps, Some(6));
+        assert!(analysis.lcm_shortcut_valid);
+
+        let (directions, graph) = parse(OFFSET_CYCLES.as_bytes()).expect("Unable to read graph");
+        let analysis = cycles::analyse(
+            &graph,
+            &ids(&graph, &["11A", "22A"]),
+            |id| graph.label(id).ends_with('Z'),
+            &directions,
+        )
+        .expect("Valid graph");
+        assert_eq!(
+            analysis.cycles[0],
+            Cycle {
+                entry: 1,
+                length: 2,
+                prefix_hits: vec![],
+                cycle_hits: vec![2],
+            }
+        );
+        assert_eq!(
+            analysis.cycles[1],
+            Cycle {
+                entry: 1,
+                length: 3,
+                prefix_hits: vec![],
+                cycle_hits: vec![1],
+            }
+        );
+        assert_eq!(analysis.steps, Some(4));
+        assert_eq!(analysis.lcm_shortcut, Some(arith::Lcm::Small(2)));
+        assert!(!analysis.lcm_shortcut_valid);
+        assert_eq!(brute_force_ghosts(&directions, &graph, 100), Some(4));
+
+        let (directions, graph) = parse(MULTIPLE_HITS.as_bytes()).expect("Unable to read graph");
+        let analysis = cycles::analyse(
+            &graph,
+            &ids(&graph, &["33A", "44A"]),
+            |id| graph.label(id).ends_with('Z'),
+            &directions,
+        )
+        .expect("Valid graph");
+        assert_eq!(analysis.cycles[0].cycle_hits, vec![1, 2, 6, 7]);
+        assert_eq!(analysis.cycles[1].cycle_hits, vec![3, 6]);
+        assert_eq!(analysis.steps, Some(6));
+        assert_eq!(
+            analysis.steps,
+            brute_force_ghosts(&directions, &graph, 1000)
+        );
+
+        let (directions, graph) = parse(LATE_CYCLE_HIT.as_bytes()).expect("Unable to read graph");
+        let analysis = cycles::analyse(
+            &graph,
+            &ids(&graph, &["11A", "22A"]),
+            |id| graph.label(id).ends_with('Z'),
+            &directions,
+        )
+        .expect("Valid graph");
+        assert_eq!(analysis.cycles[0].prefix_hits, vec![3]);
+        assert_eq!(analysis.cycles[1].cycle_hits, vec![8]);
+        assert_eq!(analysis.steps, None);
+        assert_eq!(brute_force_ghosts(&directions, &graph, 1000), None);
+        assert!(solve_part2(&directions, &graph).is_err());
+    }
+
+    #[test]
+    fn generalised_crt() {
+        assert_eq!(cycles::crt(2, 3, 3, 5), Ok(Some((8, 15))));
+        // Non-coprime moduli
+        assert_eq!(cycles::crt(1, 4, 3, 6), Ok(Some((9, 12))));
+        assert_eq!(cycles::crt(0, 4, 1, 6), Ok(None));
+        assert_eq!(cycles::crt(0, 1 << 127, 1, 3), Err(WalkError::StepOverflow));
+    }
+
+    #[test]
+    fn walk_errors() {
+        let (directions, graph) = parse(OFFSET_CYCLES.as_bytes()).expect("Unable to read graph");
+        // 11A never reaches 22Z
+        assert_eq!(
+            graph.num_steps(
+                graph.id("11A").expect("Known label"),
+                |id| graph.label(id) == "22Z",
+                &directions
+            ),
+            Err(WalkError::Unreachable {
+                cycle_entry: 1,
+                cycle_len: 2
+            })
+        );
+        assert_eq!(
+            solve_part1(&directions, &graph),
+            Err(WalkError::MissingNode(String::from("AAA")))
+        );
+
+        let (directions, graph) = parse("LR\n\nAAA = (BBB, CCC)\nBBB = (AAA, DDD)\n".as_bytes())
+            .expect("Unable to read graph");
+        assert_eq!(
+            solve_part1(&directions, &graph),
+            Err(WalkError::DanglingRight {
+                from: String::from("BBB"),
+                to: String::from("DDD")
+            })
+        );
+        // CCC is only ever referenced
+        assert_eq!(
+            graph.num_steps(
+                graph.id("CCC").expect("Known label"),
+                |id| graph.label(id) == "ZZZ",
+                &directions
+            ),
+            Err(WalkError::MissingNode(String::from("CCC")))
+        );
+        assert_eq!(
+            graph.num_steps(graph.id("AAA").expect("Known label"), |_| false, &[]),
+            Err(WalkError::NoDirections)
+        );
+    }
+
+    #[test]
+    fn long_labels() {
+        let input =
+            "RL\n\nSTART = (LEFT, EXIT_Z)\nLEFT = (START, START)\nEXIT_Z = (EXIT_Z, EXIT_Z)\n";
+        let (directions, graph) = parse(input.as_bytes()).expect("Unable to read graph");
+        assert_eq!(graph.len(), 3);
+        let start = graph.id("START").expect("Known label");
+        let node = graph.get_node(start).expect("Defined node");
+        assert_eq!(node.right().map(|id| graph.label(id)), Some("EXIT_Z"));
+        assert_eq!(
+            graph.num_steps(start, |id| graph.label(id) == "EXIT_Z", &directions),
+            Ok(1)
+        );
+        assert!(parse("L\n\nAAA = BBB, CCC\n".as_bytes()).is_err());
+        assert!(parse("X\n\nAAA = (BBB, CCC)\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn exports() {
+        let (directions, graph) = parse(SAMPLE_1.as_bytes()).expect("Unable to read graph");
+        let start = graph.id("AAA").expect("Known label");
+        let path = graph
+            .path(start, |id| graph.label(id) == "ZZZ", &directions)
+            .expect("Reachable");
+        let labels: Vec<&str> = path.iter().map(|id| graph.label(*id)).collect();
+        assert_eq!(
+            labels,
+            vec!["AAA", "BBB", "AAA", "BBB", "AAA", "BBB", "ZZZ"]
+        );
+
+        let dot = export::to_dot(&graph, Some(&path));
+        assert_eq!(
+            dot,
+            "digraph network {
+    \"AAA\" [style=filled, fillcolor=palegreen];
+    \"ZZZ\" [style=filled, fillcolor=salmon];
+    \"AAA\" -> \"BBB\" [label=\"LR\", color=blue, penwidth=3];
+    \"BBB\" -> \"AAA\" [label=\"L\", color=blue, penwidth=3];
+    \"BBB\" -> \"ZZZ\" [label=\"R\", color=blue, penwidth=3];
+    \"ZZZ\" -> \"ZZZ\" [label=\"LR\"];
+}
+"
+        );
+        assert_eq!(
+            export::to_json(&graph),
+            "{\"nodes\": [\
+             {\"id\": \"AAA\", \"successors\": [\"BBB\", \"BBB\"], \"start\": true, \"end\": false}, \
+             {\"id\": \"BBB\", \"successors\": [\"AAA\", \"ZZZ\"], \"start\": false, \"end\": false}, \
+             {\"id\": \"ZZZ\", \"successors\": [\"ZZZ\", \"ZZZ\"], \"start\": false, \"end\": true}]}"
+        );
+
+        // Control characters in labels are escaped rather than written raw
+        let (_, graph) = parse("L\n\nA\tA = (\"Z\", ZZZ)\n\"Z\" = (ZZZ, ZZZ)\n".as_bytes())
+            .expect("Valid input");
+        let json = export::to_json(&graph);
+        assert!(json.contains("{\"id\": \"A\\u0009A\", \"successors\": [\"\\\"Z\\\"\""));
+        assert!(!json.chars().any(|c| c.is_control()));
+    }
+
+    // Three-way branches, with 0, 1 and 2 picking the successor
+    const TERNARY: &str = "0212
+
+11A = (11B, 11C, 11A)
+11B = (11C, 11Z, 11B)
+11C = (11Z, 11A, 11C)
+11Z = (11A, 11B, 11Z)
+22A = (22Z, 22A, 22A)
+22Z = (22A, 22Z, 22A)
+";
+
+    #[test]
+    fn branching_nodes() {
+        let (directions, graph) = parse(TERNARY.as_bytes()).expect("Unable to read graph");
+        assert_eq!(directions[1], Direction::Branch(2));
+        let node = graph
+            .get_node(graph.id("11A").expect("Known label"))
+            .expect("Defined node");
+        assert_eq!(node.successors.len(), 3);
+
+        let starts = ids(&graph, &["11A", "22A"]);
+        let steps = graph.num_steps(starts[0], |id| graph.label(id) == "11Z", &directions);
+        assert_eq!(steps, Ok(3));
+        let analysis = cycles::analyse(
+            &graph,
+            &starts,
+            |id| graph.label(id).ends_with('Z'),
+            &directions,
+        )
+        .expect("Valid graph");
+        assert_eq!(
+            analysis.steps,
+            brute_force_ghosts(&directions, &graph, 1000)
+        );
+        assert!(analysis.steps.is_some());
+
+        // Letters name branches too, and binary nodes have no third branch
+        let (directions, graph) =
+            parse("c\n\nAAA = (BBB, CCC)\n".as_bytes()).expect("Unable to read graph");
+        assert_eq!(
+            solve_part1(&directions, &graph),
+            Err(WalkError::MissingBranch {
+                from: String::from("AAA"),
+                branch: 2
+            })
+        );
+    }
+
+    #[test]
+    fn lockstep_simulation() {
+        let (directions, graph) = parse(SAMPLE_2.as_bytes()).expect("Unable to read graph");
+        let starts = ids(&graph, &["11A", "22A"]);
+        let is_exit = |id| graph.label(id).ends_with('Z');
+        let mut progress = Vec::new();
+        let simulation = simulate::Lockstep {
+            starts: &starts,
+            budget: 100,
+            progress_interval: 2,
+            record_snapshots: true,
+        }
+        .run(&graph, is_exit, &directions, |update| {
+            progress.push(update.step)
+        })
+        .expect("Valid graph");
+        assert_eq!(simulation.steps, Some(6));
+        assert_eq!(simulation.positions, ids(&graph, &["11Z", "22Z"]));
+        assert_eq!(progress, vec![0, 2, 4, 6]);
+        let snapshots: Vec<(usize, Vec<usize>)> = simulation
+            .snapshots
             .into_iter()
-            .reduce(|acc, e| lcm(acc, e))
-            .expect("Unable to find LCM");
-        println!("Number of moves: {num_steps}");
+            .map(|snapshot| (snapshot.step, snapshot.on_exit))
+            .collect();
+        assert_eq!(
+            snapshots,
+            vec![(2, vec![0]), (3, vec![1]), (4, vec![0]), (6, vec![0, 1])]
+        );
+
+        // The budget runs out before the walkers line up, and agrees with the cycle analysis
+        let (directions, graph) = parse(OFFSET_CYCLES.as_bytes()).expect("Unable to read graph");
+        let starts = ids(&graph, &["11A", "22A"]);
+        let is_exit = |id| graph.label(id).ends_with('Z');
+        let mut lockstep = simulate::Lockstep {
+            starts: &starts,
+            budget: 3,
+            progress_interval: 0,
+            record_snapshots: false,
+        };
+        let simulation = lockstep
+            .run(&graph, is_exit, &directions, |_| {})
+            .expect("Valid graph");
+        assert_eq!((simulation.steps, simulation.steps_taken), (None, 3));
+        assert!(simulation.snapshots.is_empty());
+        lockstep.budget = 100;
+        let simulation = lockstep
+            .run(&graph, is_exit, &directions, |_| {})
+            .expect("Valid graph");
+        let analysis = cycles::analyse(&graph, &starts, is_exit, &directions).expect("Valid graph");
+        assert_eq!(simulation.steps.map(|steps| steps as u128), analysis.steps);
+    }
+
+    // One walker per prime, each on a loop of that length with its Z just before the start,
+    // so they all line up one step before the product of the primes
+    fn prime_loops(primes: &[usize]) -> String {
+        let mut input = String::from("L\n\n");
+        for p in primes {
+            let mut labels = vec![format!("P{p}A")];
+            labels.extend((1..p - 1).map(|i| format!("P{p}N{i}")));
+            labels.push(format!("P{p}Z"));
+            for (i, label) in labels.iter().enumerate() {
+                let next = &labels[(i + 1) % labels.len()];
+                input.push_str(&format!("{label} = ({next}, {next})\n"));
+            }
+        }
+        input
+    }
+
+    #[test]
+    fn lcm_overflow() {
+        const PRIMES: [usize; 30] = [
+            2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83,
+            89, 97, 101, 103, 107, 109, 113,
+        ];
+        // Past u64 but inside u128
+        let input = prime_loops(&PRIMES[..20]);
+        let (directions, graph) = parse(input.as_bytes()).expect("Unable to read graph");
+        assert_eq!(
+            solve_part2(&directions, &graph).expect("Reachable"),
+            557940830126698960967415389
+        );
+        // Past u128, which is an error rather than a panic
+        let input = prime_loops(&PRIMES);
+        let (directions, graph) = parse(input.as_bytes()).expect("Unable to read graph");
+        let error = solve_part2(&directions, &graph).expect_err("Too many steps");
+        assert_eq!(error.to_string(), WalkError::StepOverflow.to_string());
+
+        use arith::{checked_lcm, lcm_all, Lcm, LcmError};
+
+        assert_eq!(checked_lcm(4, 6), Some(12));
+        assert_eq!(checked_lcm(usize::MAX, 2), None);
+        assert_eq!(lcm_all([2, 3, 4]), Ok(Lcm::Small(12)));
+        assert_eq!(lcm_all([]), Err(LcmError::Empty));
+        assert_eq!(lcm_all([3, 0]), Err(LcmError::Zero { index: 1 }));
+
+        // Three co-prime cycles near 2^61 overflow usize, then u128
+        let mersenne = (1 << 61) - 1;
+        let wide = lcm_all([mersenne, 1 << 61]).expect("Nonzero");
+        assert_eq!(wide, Lcm::Wide(5316911983139663489309385231907684352));
+        let big = lcm_all([mersenne, 1 << 61, (1 << 61) + 1, mersenne]).expect("Nonzero");
+        assert!(matches!(big, Lcm::Big(_)));
+        assert_eq!(big.to_u128(), None);
+        assert_eq!(
+            big.to_string(),
+            "12259964326927110866866776217202473466644069968255123456"
+        );
     }
 }
@@ -0,0 +1,65 @@
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{stdin, Read};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let (flags, paths): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let input: Box<dyn Read> = match paths.first() {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(stdin().lock()),
+    };
+    let (directions, graph) = day_8::parse(input)?;
+
+    if flags.iter().any(|flag| flag == "--dot") {
+        // Overlay the part 1 walk when there is one
+        let path = match (graph.id("AAA"), graph.id("ZZZ")) {
+            (Some(start), Some(exit)) => graph.path(start, |id| id == exit, &directions).ok(),
+            _ => None,
+        };
+        print!("{}", day_8::export::to_dot(&graph, path.as_deref()));
+        return Ok(());
+    }
+    if flags.iter().any(|flag| flag == "--json") {
+        println!("{}", day_8::export::to_json(&graph));
+        return Ok(());
+    }
+
+    if flags.iter().any(|flag| flag == "--simulate") {
+        // Walk every ghost together, reporting progress to stderr
+        let starts: Vec<_> = graph
+            .nodes()
+            .filter(|id| graph.label(*id).ends_with('A'))
+            .collect();
+        let simulation = day_8::simulate::Lockstep {
+            starts: &starts,
+            budget: 100_000_000,
+            progress_interval: 10_000_000,
+            record_snapshots: false,
+        }
+        .run(
+            &graph,
+            |id| graph.label(id).ends_with('Z'),
+            &directions,
+            |progress| {
+                eprintln!(
+                    "Step {}: {} on Z, best {} of {}",
+                    progress.step,
+                    progress.on_exit,
+                    progress.best,
+                    starts.len()
+                )
+            },
+        )?;
+        match simulation.steps {
+            Some(steps) => println!("All ghosts on Z after {steps} steps"),
+            None => println!("Gave up after {} steps", simulation.steps_taken),
+        }
+        return Ok(());
+    }
+
+    println!("Part 1: {}", day_8::solve_part1(&directions, &graph)?);
+    println!("Part 2: {}", day_8::solve_part2(&directions, &graph)?);
+    Ok(())
+}
@@ -1,82 +1,664 @@
+use num_bigint::{BigInt, Sign};
 use std::error::Error;
-use std::io::Read;
-use std::rc::Rc;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
 
-type Sequence = Vec<i64>;
+pub type Sequence = Vec<i64>;
 
-fn get_delta_sequence(seq: &Sequence) -> (Sequence, bool) {
-    let mut is_all_zero = true;
-    let mut next_seq = Vec::with_capacity(seq.len() - 1);
-    for window in seq.windows(2) {
-        let diff = window[1] - window[0];
-        if diff != 0 {
-            is_all_zero = false;
+/// What a difference table can hold: i64 for speed, or `BigInt` when the differences might
+/// not fit.
+pub trait TableValue: Sized {
+    fn checked_sub(&self, other: &Self) -> Option<Self>;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn is_zero(&self) -> bool;
+}
+
+impl TableValue for i64 {
+    fn checked_sub(&self, other: &Self) -> Option<Self> {
+        i64::checked_sub(*self, *other)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        i64::checked_add(*self, *other)
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+}
+
+impl TableValue for BigInt {
+    fn checked_sub(&self, other: &Self) -> Option<Self> {
+        Some(self - other)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.sign() == Sign::NoSign
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TableError {
+    Empty,
+    /// Ran out of values before reaching a row of zeros.
+    InsufficientLength {
+        len: usize,
+    },
+    /// A difference didn't fit, at the given row (0 being the sequence) and position.
+    Overflow {
+        row: usize,
+        index: usize,
+    },
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TableError::Empty => write!(f, "empty sequence"),
+            TableError::InsufficientLength { len } => {
+                write!(f, "{len} values never reach a row of zeros")
+            }
+            TableError::Overflow { row, index } => {
+                write!(f, "difference {index} in row {row} overflowed")
+            }
         }
-        next_seq.push(diff);
     }
-    (next_seq, is_all_zero)
 }
 
-fn extrapolate_last_value(sequences: &Vec<Rc<Sequence>>) -> Result<i64, Box<dyn Error>> {
-    let mut last_delta = *sequences
+impl Error for TableError {}
+
+/// Every difference row, from the sequence down to the first row of zeros. A row only counts
+/// as zeros if it has at least one value, so a lone nonzero value is an error.
+pub fn difference_table<T: TableValue>(sequence: Vec<T>) -> Result<Vec<Vec<T>>, TableError> {
+    if sequence.is_empty() {
+        return Err(TableError::Empty);
+    }
+    let len = sequence.len();
+    let mut rows = vec![sequence];
+    loop {
+        let row = rows.last().expect("Starts with the sequence");
+        if row.iter().all(T::is_zero) {
+            return Ok(rows);
+        }
+        if row.len() == 1 {
+            return Err(TableError::InsufficientLength { len });
+        }
+        let next = row
+            .windows(2)
+            .enumerate()
+            .map(|(index, window)| {
+                window[1]
+                    .checked_sub(&window[0])
+                    .ok_or(TableError::Overflow {
+                        row: rows.len(),
+                        index,
+                    })
+            })
+            .collect::<Result<Vec<T>, _>>()?;
+        rows.push(next);
+    }
+}
+
+pub fn extrapolate_last_value<T: TableValue + Clone>(
+    sequences: &[Vec<T>],
+) -> Result<T, Box<dyn Error>> {
+    let mut last_delta = sequences
         .last()
         .ok_or("Missing last value")?
         .last()
-        .ok_or("Missing last value")?;
+        .ok_or("Missing last value")?
+        .clone();
     for seq in sequences.iter().rev() {
-        last_delta = seq.last().ok_or("Missing last value")? + last_delta;
+        last_delta = last_delta
+            .checked_add(seq.last().ok_or("Missing last value")?)
+            .ok_or("Extrapolation overflowed")?;
     }
     Ok(last_delta)
 }
 
-fn extrapolate_first_value(sequences: &Vec<Rc<Sequence>>) -> Result<i64, Box<dyn Error>> {
-    let mut last_delta = *sequences
+pub fn extrapolate_first_value<T: TableValue + Clone>(
+    sequences: &[Vec<T>],
+) -> Result<T, Box<dyn Error>> {
+    let mut last_delta = sequences
         .last()
         .ok_or("Missing last value")?
         .first()
-        .ok_or("Missing first value")?;
+        .ok_or("Missing first value")?
+        .clone();
     for seq in sequences.iter().rev() {
-        last_delta = seq.first().ok_or("Missing last value")? - last_delta;
+        last_delta = seq
+            .first()
+            .ok_or("Missing last value")?
+            .checked_sub(&last_delta)
+            .ok_or("Extrapolation overflowed")?;
     }
     Ok(last_delta)
 }
 
+/// What the predictor knows after each value.
+#[derive(Debug, PartialEq)]
+pub struct Update {
+    pub prediction: i64,
+    /// None until a row of zeros shows up.
+    pub degree: Option<usize>,
+}
+
+/// Day 9's extrapolation over values that arrive one at a time. Only the last value of each
+/// difference row is kept, and rows below the first row of zeros are left implicit, so memory
+/// is bounded by the degree rather than the number of values.
+#[derive(Debug, Default)]
+pub struct OnlinePredictor {
+    /// Last value of each difference row, the sequence first.
+    diagonal: Vec<i64>,
+    /// How many values at the end of each row are zero.
+    zero_runs: Vec<usize>,
+    len: usize,
+}
+
+impl OnlinePredictor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn diagonal(&self) -> &[i64] {
+        &self.diagonal
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Overflow in the prediction itself is reported at row 0, one past the last value. A
+    /// rejected value leaves the predictor as it was.
+    pub fn push(&mut self, value: i64) -> Result<Update, TableError> {
+        let old_len = self.len;
+        let len = old_len + 1;
+        let mut diagonal = vec![value];
+        let mut zero_runs = Vec::new();
+        for row in 0..len {
+            let entry = diagonal[row];
+            // Rows past the stored ones are all zeros, old_len - row of them
+            let previous_run = self
+                .zero_runs
+                .get(row)
+                .copied()
+                .unwrap_or(old_len.saturating_sub(row));
+            zero_runs.push(if entry == 0 { previous_run + 1 } else { 0 });
+            let above = self.diagonal.get(row).copied().unwrap_or(0);
+            if entry == 0 && row >= self.diagonal.len() {
+                break;
+            }
+            if row + 1 < len {
+                let next = entry.checked_sub(above).ok_or(TableError::Overflow {
+                    row: row + 1,
+                    index: len - row - 2,
+                })?;
+                diagonal.push(next);
+            }
+        }
+        // Keep down to the first row that is nothing but zeros
+        if let Some(zero_row) = (0..zero_runs.len()).find(|row| zero_runs[*row] == len - row) {
+            diagonal.truncate(zero_row + 1);
+            zero_runs.truncate(zero_row + 1);
+        }
+        let update = OnlinePredictor::update(&diagonal, &zero_runs, len)?;
+        self.diagonal = diagonal;
+        self.zero_runs = zero_runs;
+        self.len = len;
+        Ok(update)
+    }
+
+    fn update(diagonal: &[i64], zero_runs: &[usize], len: usize) -> Result<Update, TableError> {
+        let degree = (0..zero_runs.len())
+            .find(|row| zero_runs[*row] == len - row)
+            .map(|row| row.saturating_sub(1));
+        let prediction = diagonal
+            .iter()
+            .try_fold(0i64, |total, entry| total.checked_add(*entry))
+            .ok_or(TableError::Overflow { row: 0, index: len })?;
+        Ok(Update { prediction, degree })
+    }
+}
+
+// Leading entry of each difference row, down to the first all-zero row. Differences are
+// taken in place so only one row is held at a time.
+fn leading_differences(seq: &[i64]) -> Result<Vec<i128>, Box<dyn Error>> {
+    let mut row: Vec<i128> = seq.iter().map(|value| *value as i128).collect();
+    let mut leading = Vec::new();
+    while row.iter().any(|value| *value != 0) {
+        leading.push(row[0]);
+        for i in 0..row.len() - 1 {
+            row[i] = row[i + 1]
+                .checked_sub(row[i])
+                .ok_or("Difference overflowed")?;
+        }
+        row.pop();
+    }
+    Ok(leading)
+}
+
+/// Value `offset` steps past the last one, so 1 is the next value and `-(seq.len())` is the
+/// one before the first. Uses Newton's forward-difference formula,
+/// f(x) = sum of C(x, j) * (jth difference at 0), so far offsets cost no more than near ones.
+pub fn predict(seq: &[i64], offset: i64) -> Result<i64, Box<dyn Error>> {
+    if seq.is_empty() {
+        return Err("Can't predict from an empty sequence".into());
+    }
+    let x = (seq.len() as i128 - 1) + offset as i128;
+    let mut total = 0i128;
+    // C(x, j) for signed x, stepped with C(x, j + 1) = C(x, j) * (x - j) / (j + 1), which
+    // always divides exactly
+    let mut binomial = 1i128;
+    for (j, difference) in leading_differences(seq)?.into_iter().enumerate() {
+        let term = binomial
+            .checked_mul(difference)
+            .ok_or("Prediction overflowed")?;
+        total = total.checked_add(term).ok_or("Prediction overflowed")?;
+        binomial = binomial
+            .checked_mul(x - j as i128)
+            .ok_or("Prediction overflowed")?
+            / (j as i128 + 1);
+    }
+    Ok(i64::try_from(total).map_err(|_| "Prediction overflowed")?)
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.abs()
+}
+
+/// An exact fraction in lowest terms, with a positive denominator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ratio {
+    pub numerator: i128,
+    pub denominator: i128,
+}
+
+impl Ratio {
+    pub fn new(numerator: i128, denominator: i128) -> Self {
+        let divisor = gcd(numerator, denominator).max(1) * denominator.signum();
+        Ratio {
+            numerator: numerator / divisor,
+            denominator: denominator / divisor,
+        }
+    }
+}
+
+impl fmt::Display for Ratio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FitError {
+    Empty,
+    /// No difference row came out all zero, so the values alone can't pin the degree down.
+    NotPolynomial {
+        degree_below: usize,
+    },
+    Overflow,
+}
+
+impl fmt::Display for FitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FitError::Empty => write!(f, "empty sequence"),
+            FitError::NotPolynomial { degree_below } => {
+                write!(f, "not polynomial of degree < {degree_below}")
+            }
+            FitError::Overflow => write!(f, "coefficients overflowed"),
+        }
+    }
+}
+
+impl Error for FitError {}
+
+/// The polynomial through a sequence, taking its first value to be at x = 0. Coefficients
+/// share one denominator so evaluating stays in integers.
+#[derive(Debug, PartialEq)]
+pub struct Polynomial {
+    /// Constant term first.
+    numerators: Vec<i128>,
+    denominator: i128,
+}
+
+impl Polynomial {
+    pub fn degree(&self) -> usize {
+        self.numerators.len() - 1
+    }
+
+    /// Constant term first.
+    pub fn coefficients(&self) -> Vec<Ratio> {
+        self.numerators
+            .iter()
+            .map(|numerator| Ratio::new(*numerator, self.denominator))
+            .collect()
+    }
+
+    pub fn evaluate(&self, x: i64) -> Result<Ratio, FitError> {
+        // Horner's rule
+        let mut total = 0i128;
+        for numerator in self.numerators.iter().rev() {
+            total = total
+                .checked_mul(x as i128)
+                .and_then(|total| total.checked_add(*numerator))
+                .ok_or(FitError::Overflow)?;
+        }
+        Ok(Ratio::new(total, self.denominator))
+    }
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms: Vec<(usize, Ratio)> = self
+            .coefficients()
+            .into_iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, coefficient)| coefficient.numerator != 0)
+            .collect();
+        if terms.is_empty() {
+            return write!(f, "0");
+        }
+        let mut first = true;
+        for (power, coefficient) in terms {
+            let magnitude = Ratio::new(coefficient.numerator.abs(), coefficient.denominator);
+            match (first, coefficient.numerator < 0) {
+                (true, true) => write!(f, "-")?,
+                (false, true) => write!(f, " - ")?,
+                (false, false) => write!(f, " + ")?,
+                (true, false) => {}
+            }
+            if magnitude != Ratio::new(1, 1) || power == 0 {
+                write!(f, "{magnitude}")?;
+            }
+            match power {
+                0 => {}
+                1 => write!(f, "x")?,
+                _ => write!(f, "x^{power}")?,
+            }
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+/// Minimal-degree polynomial through every value, with exact coefficients. Expands Newton's
+/// form, the sum of (jth difference at 0) * x(x - 1)...(x - j + 1) / j!, over a shared
+/// denominator of degree!.
+pub fn fit(seq: &[i64]) -> Result<Polynomial, FitError> {
+    if seq.is_empty() {
+        return Err(FitError::Empty);
+    }
+    let leading = leading_differences(seq).map_err(|_| FitError::Overflow)?;
+    // The zero row has to have at least one value in it to count
+    if leading.len() >= seq.len() {
+        return Err(FitError::NotPolynomial {
+            degree_below: seq.len() - 1,
+        });
+    }
+    let degree = leading.len().saturating_sub(1);
+    let denominator = (1..=degree as i128)
+        .try_fold(1i128, |product, n| product.checked_mul(n))
+        .ok_or(FitError::Overflow)?;
+
+    let mut numerators = vec![0i128; degree + 1];
+    // Falling factorial x(x - 1)...(x - j + 1), constant term first
+    let mut falling = vec![1i128];
+    let mut factorial = 1i128;
+    for (j, difference) in leading.iter().enumerate() {
+        if j > 0 {
+            factorial = factorial.checked_mul(j as i128).ok_or(FitError::Overflow)?;
+            let mut next = vec![0i128; falling.len() + 1];
+            for (power, coefficient) in falling.iter().enumerate() {
+                next[power + 1] = next[power + 1]
+                    .checked_add(*coefficient)
+                    .ok_or(FitError::Overflow)?;
+                next[power] = coefficient
+                    .checked_mul(-(j as i128 - 1))
+                    .and_then(|product| product.checked_add(next[power]))
+                    .ok_or(FitError::Overflow)?;
+            }
+            falling = next;
+        }
+        let scale = difference
+            .checked_mul(denominator / factorial)
+            .ok_or(FitError::Overflow)?;
+        for (power, coefficient) in falling.iter().enumerate() {
+            numerators[power] = coefficient
+                .checked_mul(scale)
+                .and_then(|term| term.checked_add(numerators[power]))
+                .ok_or(FitError::Overflow)?;
+        }
+    }
+    Ok(Polynomial {
+        numerators,
+        denominator,
+    })
+}
+
+pub fn parse(input: impl Read) -> Result<Vec<Sequence>, Box<dyn Error>> {
+    let mut sequences = Vec::new();
+    for line in BufReader::new(input).lines() {
+        let sequence: Sequence = line?
+            .split_whitespace()
+            .map(|s| s.parse())
+            .collect::<Result<_, _>>()?;
+        sequences.push(sequence);
+    }
+    Ok(sequences)
+}
+
+pub fn solve_part1(sequences: &[Sequence]) -> Result<i64, Box<dyn Error>> {
+    let mut total_last = 0i64;
+    for sequence in sequences {
+        let next = extrapolate_last_value(&difference_table(sequence.clone())?)?;
+        total_last = total_last.checked_add(next).ok_or("Total overflowed")?;
+    }
+    Ok(total_last)
+}
+
+pub fn solve_part2(sequences: &[Sequence]) -> Result<i64, Box<dyn Error>> {
+    let mut total_first = 0i64;
+    for sequence in sequences {
+        let previous = extrapolate_first_value(&difference_table(sequence.clone())?)?;
+        total_first = total_first
+            .checked_add(previous)
+            .ok_or("Total overflowed")?;
+    }
+    Ok(total_first)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::error::Error;
-    use std::fs::File;
-    use std::io::{BufRead, BufReader};
-    use std::rc::Rc;
+
+    const SAMPLE: &str = "0 3 6 9 12 15
+1 3 6 10 15 21
+10 13 16 21 30 45
+";
 
     #[test]
     fn part_1_and_2() -> Result<(), Box<dyn Error>> {
-        let mut lines = BufReader::new(File::open("input")?).lines();
-        let mut total_last = 0;
-        let mut total_first = 0;
-        while let Some(Ok(line)) = lines.next() {
-            let sequence: Sequence = line
-                .split_whitespace()
-                .map(|s| s.parse().expect("Invalid number value"))
-                .collect();
-
-            let mut delta_sequences: Vec<Rc<Sequence>> = Vec::new();
-            let mut sequence_rc = Rc::new(sequence);
-            delta_sequences.push(Rc::clone(&sequence_rc));
-            loop {
-                let (next_sequence, end) = get_delta_sequence(sequence_rc.as_ref());
-                let next_sequence_rc = Rc::new(next_sequence);
-                delta_sequences.push(Rc::clone(&next_sequence_rc));
-                sequence_rc = next_sequence_rc;
-                if end {
-                    break;
-                }
+        let sequences = parse(SAMPLE.as_bytes())?;
+        assert_eq!(solve_part1(&sequences)?, 114);
+        assert_eq!(solve_part2(&sequences)?, 2);
+
+        // Each prediction fits, but their sum doesn't
+        let sequences = vec![vec![i64::MAX, i64::MAX]; 2];
+        assert!(solve_part1(&sequences).is_err());
+        assert!(solve_part2(&sequences).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn predict_offsets() -> Result<(), Box<dyn Error>> {
+        for sequence in parse(SAMPLE.as_bytes())? {
+            let rows = difference_table(sequence.clone())?;
+            assert_eq!(predict(&sequence, 1)?, extrapolate_last_value(&rows)?);
+            assert_eq!(
+                predict(&sequence, -(sequence.len() as i64))?,
+                extrapolate_first_value(&rows)?
+            );
+            assert_eq!(predict(&sequence, 0)?, *sequence.last().expect("Not empty"));
+        }
+        // Triangular numbers, (n + 1)(n + 2) / 2 at index n
+        let triangular = [1, 3, 6, 10, 15, 21];
+        assert_eq!(predict(&triangular, 100)?, 106 * 107 / 2);
+        assert_eq!(predict(&triangular, -7)?, 0);
+        assert_eq!(predict(&[7], 500)?, 7);
+        assert!(predict(&[], 1).is_err());
+        assert!(predict(&[0, i64::MAX], 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn polynomial_fit() -> Result<(), Box<dyn Error>> {
+        let fits: Vec<String> = parse(SAMPLE.as_bytes())?
+            .iter()
+            .map(|sequence| fit(sequence).map(|polynomial| polynomial.to_string()))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(
+            fits,
+            vec!["3x", "1/2x^2 + 3/2x + 1", "1/3x^3 - x^2 + 11/3x + 10"]
+        );
+
+        let polynomial = fit(&[10, 13, 16, 21, 30, 45])?;
+        assert_eq!(polynomial.degree(), 3);
+        assert_eq!(polynomial.coefficients()[1], Ratio::new(11, 3));
+        assert_eq!(polynomial.evaluate(6)?, Ratio::new(68, 1));
+        assert_eq!(polynomial.evaluate(-1)?, Ratio::new(5, 1));
+
+        assert_eq!(fit(&[0, 0])?.to_string(), "0");
+        assert_eq!(fit(&[-4, -4, -4])?.to_string(), "-4");
+        assert_eq!(
+            fit(&[1, 2, 4, 8]),
+            Err(FitError::NotPolynomial { degree_below: 3 })
+        );
+        assert_eq!(
+            FitError::NotPolynomial { degree_below: 3 }.to_string(),
+            "not polynomial of degree < 3"
+        );
+        assert_eq!(fit(&[]), Err(FitError::Empty));
+
+        // C(x, 34) fits in an i64 for x < 36, but 34! doesn't fit in an i128
+        let mut binomial = vec![0i64; 34];
+        binomial.extend([1, 35]);
+        assert_eq!(fit(&binomial), Err(FitError::Overflow));
+        Ok(())
+    }
+
+    #[test]
+    fn difference_table_errors() -> Result<(), Box<dyn Error>> {
+        assert_eq!(difference_table::<i64>(vec![]), Err(TableError::Empty));
+        assert_eq!(
+            difference_table(vec![5]),
+            Err(TableError::InsufficientLength { len: 1 })
+        );
+        assert_eq!(
+            difference_table(vec![1, 2, 4, 8]),
+            Err(TableError::InsufficientLength { len: 4 })
+        );
+        assert_eq!(difference_table(vec![0])?.len(), 1);
+        assert_eq!(difference_table(vec![5, 5])?.len(), 2);
+
+        // A quadratic whose values fit but whose first differences don't
+        let sequence = vec![i64::MIN, 8, 8, i64::MIN];
+        assert_eq!(
+            difference_table(sequence.clone()),
+            Err(TableError::Overflow { row: 1, index: 0 })
+        );
+        let rows = difference_table(sequence.into_iter().map(BigInt::from).collect())?;
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[1][0].to_string(), "9223372036854775816");
+        let next = extrapolate_last_value(&rows)?;
+        assert_eq!(next.to_string(), "-27670116110564327440");
+        assert!(i64::try_from(&next).is_err());
+        assert_eq!(
+            extrapolate_first_value(&rows)?.to_string(),
+            "-27670116110564327440"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn online_predictor() -> Result<(), Box<dyn Error>> {
+        for sequence in parse(SAMPLE.as_bytes())? {
+            let mut predictor = OnlinePredictor::new();
+            for (i, value) in sequence.iter().enumerate() {
+                let update = predictor.push(*value)?;
+                assert_eq!(update.prediction, predict(&sequence[..=i], 1)?);
             }
-            total_last += extrapolate_last_value(&delta_sequences)?;
-            total_first += extrapolate_first_value(&delta_sequences)?;
+            let rows = difference_table(sequence.clone())?;
+            assert_eq!(
+                predictor.push(extrapolate_last_value(&rows)?)?.degree,
+                Some(fit(&sequence)?.degree())
+            );
+        }
+
+        // Degree shows up once a row of zeros does, and the diagonal stays short
+        let mut predictor = OnlinePredictor::new();
+        let degrees: Vec<Option<usize>> = (0..200i64)
+            .map(|x| predictor.push(x * x - 3).map(|update| update.degree))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(&degrees[..5], &[None, None, None, Some(2), Some(2)]);
+        assert_eq!(predictor.diagonal().len(), 4);
+        assert_eq!(predictor.push(200 * 200 - 3)?.prediction, 201 * 201 - 3);
+
+        // A value off the pattern brings the implicit rows back
+        assert_eq!(predictor.push(0)?.degree, None);
+        assert_eq!(predictor.len(), 202);
+        let sequence = [0, 1, 4, 9, 16, 25, 7, -2, 30];
+        let mut predictor = OnlinePredictor::new();
+        for (i, value) in sequence.iter().enumerate() {
+            assert_eq!(
+                predictor.push(*value)?.prediction,
+                predict(&sequence[..=i], 1)?
+            );
+        }
+
+        let mut zeros = OnlinePredictor::new();
+        assert_eq!(
+            zeros.push(0)?,
+            Update {
+                prediction: 0,
+                degree: Some(0)
+            }
+        );
+        assert!(OnlinePredictor::new().push(i64::MIN)?.degree.is_none());
+        let mut overflow = OnlinePredictor::new();
+        overflow.push(i64::MIN)?;
+        assert_eq!(
+            overflow.push(8),
+            Err(TableError::Overflow { row: 1, index: 0 })
+        );
+        assert_eq!(overflow.len(), 1);
+
+        // A rejected value is forgotten, so later pushes carry on from before it
+        let mut predictor = OnlinePredictor::new();
+        for value in [1, 2, 3] {
+            predictor.push(value)?;
         }
-        println!("Total for extrapolating at end: {}", total_last);
-        println!("Total for extrapolating at beginning: {}", total_first);
+        assert!(predictor.push(i64::MIN).is_err());
+        assert_eq!(predictor.push(5)?.prediction, 9);
+        assert_eq!(predictor.len(), 4);
+        assert_eq!(predictor.diagonal(), &[5, 2, 1, 1]);
         Ok(())
     }
 }
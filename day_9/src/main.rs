@@ -0,0 +1,28 @@
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{stdin, Read};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let (flags, paths): (Vec<String>, Vec<String>) =
+        env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let input: Box<dyn Read> = match paths.first() {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(stdin().lock()),
+    };
+    let sequences = day_9::parse(input)?;
+
+    if flags.iter().any(|flag| flag == "--fit") {
+        for sequence in &sequences {
+            match day_9::fit(sequence) {
+                Ok(polynomial) => println!("degree {}: {}", polynomial.degree(), polynomial),
+                Err(error) => println!("{error}"),
+            }
+        }
+        return Ok(());
+    }
+
+    println!("Part 1: {}", day_9::solve_part1(&sequences)?);
+    println!("Part 2: {}", day_9::solve_part2(&sequences)?);
+    Ok(())
+}